@@ -0,0 +1,111 @@
+use std::fmt;
+
+use near_sdk::{env, FunctionError};
+
+// Every message the contract fails with, so clients can match on these instead of whatever text happens to be
+// in a `require!`. They're part of the contract's interface: changing one breaks clients that compare against it.
+// Messages mentioning a limit spell it out, and `error_messages_match_the_limits_they_mention` keeps them in sync.
+pub const ERR_UNSUPPORTED_KEY_CURVE: &str = "unsupported key curve";
+pub const ERR_ONLY_ED25519: &str = "Only ed25519 keys are supported";
+pub const ERR_SIGNATURE_MISMATCH: &str = "Signature doesn't match";
+pub const ERR_EMPTY_DATA_ID: &str = "data_id must not be empty";
+pub const ERR_DATA_ID_TOO_LONG: &str = "data_id must be at most 256 bytes long";
+pub const ERR_EMPTY_DATA_IDS: &str = "data_ids must not be empty";
+pub const ERR_SELF_GRANT: &str = "Grantee can't be the owner";
+pub const ERR_ALREADY_EXPIRED: &str = "Grant would already be expired";
+pub const ERR_INVALID_LABEL: &str = "label must be 1 to 64 bytes long";
+pub const ERR_INVALID_IDEMPOTENCY_KEY: &str = "idempotency_key must be 1 to 64 bytes long";
+pub const ERR_IDEMPOTENCY_KEY_REUSED: &str =
+    "idempotency_key was already used for another grantee or data_id";
+pub const ERR_TOO_MANY_CO_OWNERS: &str = "a grant can have at most 8 co_owners";
+pub const ERR_LOCKED_UNTIL_WRONG_UNIT: &str = "locked_until appears to be in the wrong unit";
+pub const ERR_DURATION_TOO_LARGE: &str = "duration too large";
+pub const ERR_DATA_ID_CLAIMED: &str = "data_id owned by another account";
+pub const ERR_PAUSED: &str = "Contract is paused";
+// Only when `set_maintenance` isn't given a message of its own.
+pub const ERR_MAINTENANCE: &str = "Contract is under maintenance";
+pub const ERR_NO_STATE_TO_MIGRATE: &str = "No state to migrate";
+pub const ERR_OWNER_FROZEN: &str = "Owner's grants are frozen";
+pub const ERR_NOT_CONTRACT_ACCOUNT: &str = "Only the contract account can do this";
+pub const ERR_GRANT_EXISTS: &str = "Grant already exists";
+pub const ERR_GRANT_DOES_NOT_EXIST: &str = "Grant doesn't exist";
+pub const ERR_GRANT_MISMATCH: &str = "Grant doesn't match the expected fields";
+pub const ERR_TIMELOCKED: &str = "Grant is timelocked";
+pub const ERR_NO_TIMELOCK_TO_SETTLE: &str = "Grant has no timelock to settle";
+pub const ERR_IMMUTABLE: &str = "Grant is immutable";
+pub const ERR_CONSENT_USED: &str = "Consent was already used";
+pub const ERR_NOT_GRANT_OWNER: &str = "Only the grant's owner can delete it";
+pub const ERR_NOT_GRANT_OWNER_REASSIGN: &str = "Only the grant's owner can reassign it";
+pub const ERR_NOT_GRANT_OWNER_SETTLE: &str = "Only the grant's owner can settle it";
+pub const ERR_NOT_REQUIRED_ATTESTOR: &str = "Only the grant's required attestor can attest it";
+pub const ERR_BATCH_TOO_LARGE: &str = "Batch is larger than MAX_BATCH_SIZE (32)";
+pub const ERR_TOO_MANY_RESULTS: &str =
+    "More than 100 grants match, use find_grants_page or pass a limit";
+pub const ERR_N_TOO_LARGE: &str = "n must be at most 100";
+pub const ERR_LIMIT_TOO_LARGE: &str = "limit must be at most 100";
+pub const ERR_OWNER_OR_GRANTEE_REQUIRED: &str = "Required argument: `owner` and/or `grantee`";
+pub const ERR_FILTER_REQUIRED: &str = "Required argument: `owner`, `grantee` and/or `data_id`";
+pub const ERR_CURSOR_INVALID: &str = "Cursor is no longer valid";
+
+// What the `try_*` methods fail with. Each variant displays as the exact message the matching panicking
+// method fails with, so clients see the same text whichever flavour they call.
+#[derive(Debug, PartialEq)]
+pub enum RegistryError {
+    UnsupportedKeyCurve,
+    EmptyDataId,
+    DataIdTooLong,
+    SelfGrant,
+    AlreadyExpired,
+    InvalidLabel,
+    Maintenance(String),
+    Paused,
+    OwnerFrozen,
+    GrantAlreadyExists,
+    GrantDoesNotExist,
+    NotGrantOwner,
+    GrantMismatch,
+    GrantIsTimelocked,
+    GrantIsImmutable,
+    DurationTooLarge,
+    DataIdClaimed,
+    TooManyCoOwners,
+    LockedUntilWrongUnit,
+    InvalidIdempotencyKey,
+    IdempotencyKeyReused,
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            RegistryError::UnsupportedKeyCurve => ERR_UNSUPPORTED_KEY_CURVE,
+            RegistryError::EmptyDataId => ERR_EMPTY_DATA_ID,
+            RegistryError::DataIdTooLong => ERR_DATA_ID_TOO_LONG,
+            RegistryError::SelfGrant => ERR_SELF_GRANT,
+            RegistryError::AlreadyExpired => ERR_ALREADY_EXPIRED,
+            RegistryError::InvalidLabel => ERR_INVALID_LABEL,
+            RegistryError::Maintenance(message) => message,
+            RegistryError::Paused => ERR_PAUSED,
+            RegistryError::OwnerFrozen => ERR_OWNER_FROZEN,
+            RegistryError::GrantAlreadyExists => ERR_GRANT_EXISTS,
+            RegistryError::GrantDoesNotExist => ERR_GRANT_DOES_NOT_EXIST,
+            RegistryError::NotGrantOwner => ERR_NOT_GRANT_OWNER,
+            RegistryError::GrantMismatch => ERR_GRANT_MISMATCH,
+            RegistryError::GrantIsTimelocked => ERR_TIMELOCKED,
+            RegistryError::GrantIsImmutable => ERR_IMMUTABLE,
+            RegistryError::DurationTooLarge => ERR_DURATION_TOO_LARGE,
+            RegistryError::DataIdClaimed => ERR_DATA_ID_CLAIMED,
+            RegistryError::TooManyCoOwners => ERR_TOO_MANY_CO_OWNERS,
+            RegistryError::LockedUntilWrongUnit => ERR_LOCKED_UNTIL_WRONG_UNIT,
+            RegistryError::InvalidIdempotencyKey => ERR_INVALID_IDEMPOTENCY_KEY,
+            RegistryError::IdempotencyKeyReused => ERR_IDEMPOTENCY_KEY_REUSED,
+        };
+
+        write!(f, "{}", message)
+    }
+}
+
+impl FunctionError for RegistryError {
+    fn panic(&self) -> ! {
+        env::panic_str(&self.to_string())
+    }
+}
@@ -2,12 +2,23 @@
 #![allow(clippy::too_many_arguments)]
 extern crate near_sdk;
 
-use std::convert::TryInto;
+#[cfg(test)]
+extern crate ed25519_dalek;
+
+use std::convert::{TryFrom, TryInto};
 
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::serde::Serialize;
-use near_sdk::store::LookupMap;
-use near_sdk::{env, near_bindgen, require, CurveType, EpochHeight, PublicKey};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::store::{LookupMap, LookupSet, Vector};
+use near_sdk::{
+    env, near_bindgen, require, AccountId, BlockHeight, BorshStorageKey, CurveType, EpochHeight,
+    FunctionError, NearToken, PublicKey,
+};
+
+mod error;
+mod try_api;
+pub use error::RegistryError;
+use error::*;
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -17,18 +28,507 @@ pub struct FractalRegistry {
     pub grant_ids_by_owner: LookupMap<PublicKey, Vec<String>>,
     pub grant_ids_by_grantee: LookupMap<PublicKey, Vec<String>>,
     pub grant_ids_by_data_id: LookupMap<String, Vec<String>>,
+    pub grant_ids_by_label: LookupMap<String, Vec<String>>,
+    pub attested_grant_ids: LookupSet<String>,
+    pub default_timelock_nanos_by_owner: LookupMap<PublicKey, u64>,
+    // (owner, idempotency key) -> what the key was first used for
+    pub idempotency_keys: LookupMap<(PublicKey, String), IdempotencyEntry>,
+    // Every `idempotency_keys` entry in the order it was written, from `idempotency_queue_head` up to
+    // `idempotency_queue_tail`, so expired ones can be removed oldest first.
+    pub idempotency_queue: LookupMap<u64, ((PublicKey, String), EpochHeight)>,
+    pub frozen_owners: LookupSet<PublicKey>,
+    // Every data_id with at least one grant, and where each one sits in `data_ids`.
+    pub data_ids: Vector<String>,
+    pub data_id_positions: LookupMap<String, u32>,
+    pub data_id_claims: LookupMap<String, PublicKey>,
+    pub audit_log: Vector<AuditEntry>,
+    // (claimant, delegate): owners who can grant on the claimant's claimed data_ids.
+    pub claim_delegates: LookupSet<(PublicKey, PublicKey)>,
+    // (grantee, nonce) of every consent `delete_grant_with_grantee_consent` has used, so none works twice.
+    pub used_consent_nonces: LookupSet<(PublicKey, Vec<u8>)>,
+    // The grantees `distinct_grantee_count` counts, so only those are ever taken off it.
+    pub counted_grantees: LookupSet<PublicKey>,
+
+    pub reject_self_grant: bool,
+    pub timelock_override_enabled: bool,
+    pub pause: PauseStatus,
+    // Some while in maintenance mode, holding the message writes are rejected with.
+    pub maintenance_message: Option<String>,
+    pub data_id_claims_enabled: bool,
+    pub distinct_grantee_count: u64,
+    pub audit_log_enabled: bool,
+    pub strict_locked_until: bool,
+    pub idempotency_queue_head: u64,
+    pub idempotency_queue_tail: u64,
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone)]
+// The state as deployed before any config existed. `migrate` reads this and fills in the rest with defaults.
+#[derive(BorshDeserialize)]
+struct LegacyFractalRegistry {
+    grants_by_id: LookupMap<String, Grant>,
+
+    grant_ids_by_owner: LookupMap<PublicKey, Vec<String>>,
+    grant_ids_by_grantee: LookupMap<PublicKey, Vec<String>>,
+    grant_ids_by_data_id: LookupMap<String, Vec<String>>,
+}
+
+// Every collection's prefix lives here, so new collections can't collide with existing ones.
+// The first four keep the single-byte prefixes they were deployed with; new ones just take the next free byte.
+#[derive(BorshSerialize, BorshStorageKey, Clone, Copy)]
+#[borsh(use_discriminant = true)]
+#[repr(u8)]
+pub enum StorageKey {
+    GrantsById = b'g',
+    GrantIdsByOwner = b'h',
+    GrantIdsByGrantee = b'i',
+    GrantIdsByDataId = b'j',
+    GrantIdsByLabel = b'k',
+    AttestedGrantIds = b'l',
+    DefaultTimelockNanosByOwner = b'm',
+    IdempotencyEntries = b'n',
+    FrozenOwners = b'o',
+    DataIds = b'p',
+    DataIdPositions = b'q',
+    DataIdClaims = b'r',
+    AuditLog = b's',
+    ClaimDelegates = b't',
+    IdempotencyQueue = b'v',
+    UsedConsentNonces = b'w',
+    CountedGrantees = b'x',
+}
+
+#[derive(BorshSerialize, Serialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Grant {
     owner: PublicKey,
     grantee: PublicKey,
     data_id: String,
     locked_until: EpochHeight,
+
+    // Fields below aren't part of the grant_id. New ones must go at the end (see `BorshDeserialize for Grant`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<EpochHeight>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    expired: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    required_attestor: Option<AccountId>,
+    // None for grants inserted before this was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_at: Option<EpochHeight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    valid_from: Option<EpochHeight>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    immutable: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    co_owners: Vec<PublicKey>,
+    // The account that submitted the grant for its owner, for grants inserted by signature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sponsor: Option<AccountId>,
 }
 
-#[cfg(test)]
+// Grants stored before a field was added simply end early, so missing trailing fields take their default.
+impl BorshDeserialize for Grant {
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        Ok(Self {
+            owner: BorshDeserialize::deserialize_reader(reader)?,
+            grantee: BorshDeserialize::deserialize_reader(reader)?,
+            data_id: BorshDeserialize::deserialize_reader(reader)?,
+            locked_until: BorshDeserialize::deserialize_reader(reader)?,
+            expires_at: deserialize_or_default(reader)?,
+            expired: deserialize_or_default(reader)?,
+            label: deserialize_or_default(reader)?,
+            required_attestor: deserialize_or_default(reader)?,
+            created_at: deserialize_or_default(reader)?,
+            valid_from: deserialize_or_default(reader)?,
+            immutable: deserialize_or_default(reader)?,
+            co_owners: deserialize_or_default(reader)?,
+            sponsor: deserialize_or_default(reader)?,
+        })
+    }
+}
+
+fn deserialize_or_default<T, R>(reader: &mut R) -> borsh::io::Result<T>
+where
+    T: BorshDeserialize + Default,
+    R: borsh::io::Read,
+{
+    let mut first_byte = [0u8; 1];
+    if reader.read(&mut first_byte)? == 0 {
+        return Ok(T::default());
+    }
+
+    T::deserialize_reader(&mut borsh::io::Read::chain(first_byte.as_slice(), reader))
+}
+
+// Optional settings for `insert_grant_with_options`. None of them are part of the grant_id.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde", default)]
+pub struct GrantOptions {
+    // After this, the grant no longer gives access. Unlike `locked_until`, it says nothing about revocation.
+    pub expires_at: Option<EpochHeight>,
+    // A short tag (at most `MAX_LABEL_LEN` bytes) to find the grant by, see `find_grants_by_label`.
+    pub label: Option<String>,
+    // The grant only counts as active (see `active_grants_for`) once this account has called `attest` on it.
+    pub required_attestor: Option<AccountId>,
+    // Scheduled access: the grant isn't active (see `active_grants_for`) before this.
+    pub valid_from: Option<EpochHeight>,
+    // Retrying an insert with the same key (within `IDEMPOTENCY_KEY_TTL`) returns the first insert's grant_id
+    // instead of failing with "Grant already exists", as long as that grant still exists. Keys are scoped to the
+    // owner, and reusing one for another grantee or data_id fails.
+    pub idempotency_key: Option<String>,
+    // An irrevocable grant: nothing can ever delete or change it, not its owner (even once any timelock has
+    // passed), not the contract account, and not the timelock override. Deleting it fails with
+    // "Grant is immutable"; bulk deletes and purges skip it; rotating or settling it fails the whole call.
+    pub immutable: bool,
+    // Keys (at most `MAX_CO_OWNERS`) that can delete the grant as if they owned it, with
+    // `delete_grant_as_co_owner` or `delete_grant_checked`. The owner stays the only one it's indexed under.
+    pub co_owners: Vec<PublicKey>,
+    // Filled in by the contract (see `Grant::sponsor`); JSON callers can't set it.
+    #[serde(skip)]
+    pub sponsor: Option<AccountId>,
+    // Skips grant_inserted, for bulk imports whose source of truth is elsewhere. Set through the batch methods'
+    // `silent` argument only.
+    #[serde(skip)]
+    pub silent: bool,
+}
+
+const MAX_LABEL_LEN: usize = 64;
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 64;
+// One day. Older keys are forgotten: every insert with a key removes up to `IDEMPOTENCY_KEYS_PURGED_PER_INSERT`
+// expired ones, and `purge_expired_idempotency_keys` removes more.
+const IDEMPOTENCY_KEY_TTL: u64 = 24 * 60 * 60 * 1_000_000_000;
+const IDEMPOTENCY_KEYS_PURGED_PER_INSERT: u64 = 2;
+// In bytes. data_ids are part of index keys and of every stored grant, so every insert path checks this.
+const MAX_DATA_ID_LEN: usize = 256;
+const MAX_CO_OWNERS: usize = 8;
+// With `set_strict_locked_until` on, a non-zero locked_until further back than this is taken for a timestamp
+// in seconds or milliseconds. Ten years, so any plausible nanosecond value in the past still gets through.
+const MAX_LOCKED_UNTIL_AGE: u64 = 10 * 365 * 24 * 60 * 60 * 1_000_000_000;
+
+// A grant without its owner or anything tied to the deployment it's stored on (attestations, expiry
+// bookkeeping, created_at), for moving grants with `export_owner_grants` and `import_owner_grants`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GrantInput {
+    pub grantee: PublicKey,
+    pub data_id: String,
+    pub locked_until: EpochHeight,
+    #[serde(default)]
+    pub expires_at: Option<EpochHeight>,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub required_attestor: Option<AccountId>,
+    #[serde(default)]
+    pub valid_from: Option<EpochHeight>,
+    #[serde(default)]
+    pub immutable: bool,
+    #[serde(default)]
+    pub co_owners: Vec<PublicKey>,
+}
+
+// What a caller expects a grant to look like, for `delete_grant_checked`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GrantFields {
+    pub owner: PublicKey,
+    pub grantee: PublicKey,
+    pub data_id: String,
+    pub locked_until: EpochHeight,
+    #[serde(default)]
+    pub expires_at: Option<EpochHeight>,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+// Filters for `find_grants_advanced`. Every one left out matches everything; ranges are inclusive.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde", default)]
+pub struct GrantQuery {
+    pub owner: Option<PublicKey>,
+    pub grantee: Option<PublicKey>,
+    pub data_id: Option<String>,
+    pub data_id_prefix: Option<String>,
+    pub created_from: Option<EpochHeight>,
+    pub created_to: Option<EpochHeight>,
+    pub locked_until_min: Option<EpochHeight>,
+    pub locked_until_max: Option<EpochHeight>,
+}
+
+impl GrantQuery {
+    fn matches(&self, grant: &Grant) -> bool {
+        let created_at = grant.created_at;
+
+        self.data_id_prefix
+            .as_ref()
+            .map_or(true, |prefix| grant.data_id.starts_with(prefix.as_str()))
+            && self
+                .created_from
+                .map_or(true, |from| created_at.map_or(false, |at| from <= at))
+            && self
+                .created_to
+                .map_or(true, |to| created_at.map_or(false, |at| at <= to))
+            && self
+                .locked_until_min
+                .map_or(true, |min| min <= grant.locked_until)
+            && self
+                .locked_until_max
+                .map_or(true, |max| grant.locked_until <= max)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GrantEntry {
+    grant_id: String,
+    grant: Grant,
+    is_timelocked: bool,
+}
+
+// A `Grant` whose JSON also carries its `grant_id`, next to the grant's own fields.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GrantWithId {
+    grant_id: String,
+    #[serde(flatten)]
+    grant: Grant,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum GrantField {
+    Owner,
+    Grantee,
+    DataId,
+    LockedUntil,
+    CreatedAt,
+    ExpiresAt,
+    Expired,
+    Label,
+    RequiredAttestor,
+    ValidFrom,
+    Immutable,
+    CoOwners,
+    Sponsor,
+}
+
+const ALL_GRANT_FIELDS: [GrantField; 13] = [
+    GrantField::Owner,
+    GrantField::Grantee,
+    GrantField::DataId,
+    GrantField::LockedUntil,
+    GrantField::CreatedAt,
+    GrantField::ExpiresAt,
+    GrantField::Expired,
+    GrantField::Label,
+    GrantField::RequiredAttestor,
+    GrantField::ValidFrom,
+    GrantField::Immutable,
+    GrantField::CoOwners,
+    GrantField::Sponsor,
+];
+
+// A `Grant` with only the requested fields; the others are left out of the JSON entirely. Requested fields
+// the grant itself would leave out (unset options, false flags, no co_owners) are left out too.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GrantProjection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<PublicKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grantee: Option<PublicKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locked_until: Option<EpochHeight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_at: Option<EpochHeight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<EpochHeight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expired: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    required_attestor: Option<AccountId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    valid_from: Option<EpochHeight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    immutable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    co_owners: Option<Vec<PublicKey>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sponsor: Option<AccountId>,
+}
+
+// What `try_find_grants` returns instead of panicking on a query `find_grants` would reject.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum FindResult {
+    Ok(Vec<Grant>),
+    // Neither `owner` nor `grantee` was given.
+    MissingFilter,
+    // More than `MAX_RESULTS` grants match; page through them with `find_grants_page` instead.
+    TooManyResults,
+}
+
+// `find_grants_partitioned`'s results, each side in `find_grants` order.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PartitionedGrants {
+    active: Vec<Grant>,
+    inactive: Vec<Grant>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GrantsPage {
+    grants: Vec<Grant>,
+    next_cursor: Option<String>,
+}
+
+// What `check_owner_consistency` found in an owner's index: `dangling` ids have no grant behind them
+// (see `sweep_orphans`), and `mismatched` ones belong to a grant of some other owner.
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ConsistencyReport {
+    indexed: u64,
+    dangling: u64,
+    mismatched: u64,
+}
+
+// What `prune_grants_for_owner` did: `pruned` grants are gone, `immutable` ones were left in place.
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PruneReport {
+    pruned: u64,
+    immutable: u64,
+}
+
+// What one `rebuild_owner_index` call did. `next_index` is where the next call picks up in `all_data_ids`,
+// None once it's through all of them.
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct IndexRebuild {
+    added: u64,
+    next_index: Option<u64>,
+}
+
+// One row of `owner_dashboard`: how many grants the owner has on the data_id, and the first few grantees.
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DataIdSummary {
+    data_id: String,
+    grant_count: u64,
+    sample_grantees: Vec<PublicKey>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct InsertCostEstimate {
+    storage_bytes: u64,
+    storage_cost: NearToken,
+}
+
+// `changed_by` and `changed_at` record the last `set_paused` call, and stay None until there's been one.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Default, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PauseStatus {
+    paused: bool,
+    changed_by: Option<AccountId>,
+    changed_at: Option<BlockHeight>,
+}
+
+// Every limit and setting clients may want to validate against, see `get_config`.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RegistryConfig {
+    max_batch_size: u64,
+    max_results: u64,
+    max_data_id_len: u64,
+    max_label_len: u64,
+    max_idempotency_key_len: u64,
+    idempotency_key_ttl: u64,
+    max_co_owners: u64,
+    dashboard_sample_size: u64,
+    reject_self_grant: bool,
+    strict_locked_until: bool,
+    timelock_override_enabled: bool,
+    data_id_claims_enabled: bool,
+    audit_log_enabled: bool,
+    paused: bool,
+    maintenance_message: Option<String>,
+}
+
+impl Grant {
+    fn project(self, fields: &[GrantField]) -> GrantProjection {
+        GrantProjection {
+            owner: Some(self.owner).filter(|_| fields.contains(&GrantField::Owner)),
+            grantee: Some(self.grantee).filter(|_| fields.contains(&GrantField::Grantee)),
+            data_id: Some(self.data_id).filter(|_| fields.contains(&GrantField::DataId)),
+            locked_until: Some(self.locked_until)
+                .filter(|_| fields.contains(&GrantField::LockedUntil)),
+            created_at: self
+                .created_at
+                .filter(|_| fields.contains(&GrantField::CreatedAt)),
+            expires_at: self
+                .expires_at
+                .filter(|_| fields.contains(&GrantField::ExpiresAt)),
+            expired: Some(self.expired)
+                .filter(|expired| *expired && fields.contains(&GrantField::Expired)),
+            label: self.label.filter(|_| fields.contains(&GrantField::Label)),
+            required_attestor: self
+                .required_attestor
+                .filter(|_| fields.contains(&GrantField::RequiredAttestor)),
+            valid_from: self
+                .valid_from
+                .filter(|_| fields.contains(&GrantField::ValidFrom)),
+            immutable: Some(self.immutable)
+                .filter(|immutable| *immutable && fields.contains(&GrantField::Immutable)),
+            co_owners: Some(self.co_owners).filter(|co_owners| {
+                !co_owners.is_empty() && fields.contains(&GrantField::CoOwners)
+            }),
+            sponsor: self
+                .sponsor
+                .filter(|_| fields.contains(&GrantField::Sponsor)),
+        }
+    }
+
+    fn matches(&self, fields: &GrantFields) -> bool {
+        self.owner == fields.owner
+            && self.grantee == fields.grantee
+            && self.data_id == fields.data_id
+            && self.locked_until == fields.locked_until
+            && self.expires_at == fields.expires_at
+            && self.label == fields.label
+    }
+
+    fn is_owned_by(&self, key: &PublicKey) -> bool {
+        self.owner == *key || self.co_owners.contains(key)
+    }
+
+    fn is_timelocked(&self) -> bool {
+        self.locked_until >= env::block_timestamp()
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= env::block_timestamp())
+    }
+
+    fn is_pending(&self) -> bool {
+        self.valid_from
+            .map_or(false, |valid_from| valid_from > env::block_timestamp())
+    }
+}
+
+#[cfg(all(
+    test,
+    not(feature = "sha256-grant-ids"),
+    not(feature = "short-grant-ids")
+))]
 #[test]
 fn derive_grant_id_example() {
     // Just to make sure we don't accidentally change the way we derive grant_ids.
@@ -42,6 +542,15 @@ fn derive_grant_id_example() {
             .unwrap(),
         data_id: "some data".into(),
         locked_until: 1337,
+        expires_at: None,
+        expired: false,
+        label: None,
+        required_attestor: None,
+        created_at: None,
+        valid_from: None,
+        immutable: false,
+        co_owners: vec![],
+        sponsor: None,
     };
 
     assert_eq!(
@@ -50,7 +559,34 @@ fn derive_grant_id_example() {
     );
 }
 
+#[derive(Clone, Copy)]
+pub enum GrantIdHash {
+    Keccak256,
+    Sha256,
+}
+
+// Picked at build time. Every stored grant is keyed by its id, so a deployed contract must never be rebuilt
+// with the other one: none of its existing grants could be found anymore. See the README.
+#[cfg(not(feature = "sha256-grant-ids"))]
+pub const GRANT_ID_HASH: GrantIdHash = GrantIdHash::Keccak256;
+#[cfg(feature = "sha256-grant-ids")]
+pub const GRANT_ID_HASH: GrantIdHash = GrantIdHash::Sha256;
+
+// How many bytes of the hash a grant_id keeps, also picked at build time and just as unchangeable. The short ids
+// keep 20 of them (40 hex chars); among n grants, the chance of any two colliding is about n² / 2^161.
+#[cfg(not(feature = "short-grant-ids"))]
+pub const GRANT_ID_BYTES: usize = 32;
+#[cfg(feature = "short-grant-ids")]
+pub const GRANT_ID_BYTES: usize = 20;
+
 pub fn derive_grant_id(grant: &Grant) -> String {
+    let mut grant_id = derive_grant_id_with(grant, GRANT_ID_HASH);
+    grant_id.truncate(2 * GRANT_ID_BYTES);
+
+    grant_id
+}
+
+pub fn derive_grant_id_with(grant: &Grant, hash: GrantIdHash) -> String {
     let id = format!(
         "{}{}{}{}",
         Into::<String>::into(&grant.owner),
@@ -59,21 +595,61 @@ pub fn derive_grant_id(grant: &Grant) -> String {
         grant.locked_until,
     );
 
-    hex::encode(env::keccak256(id.as_bytes()))
+    match hash {
+        GrantIdHash::Keccak256 => hex::encode(env::keccak256(id.as_bytes())),
+        GrantIdHash::Sha256 => hex::encode(env::sha256(id.as_bytes())),
+    }
 }
 
 impl Default for FractalRegistry {
     fn default() -> Self {
-        let grants_by_id = LookupMap::new(b"g");
-        let grant_ids_by_owner = LookupMap::new(b"h");
-        let grant_ids_by_grantee = LookupMap::new(b"i");
-        let grant_ids_by_data_id = LookupMap::new(b"j");
+        let grants_by_id = LookupMap::new(StorageKey::GrantsById);
+        let grant_ids_by_owner = LookupMap::new(StorageKey::GrantIdsByOwner);
+        let grant_ids_by_grantee = LookupMap::new(StorageKey::GrantIdsByGrantee);
+        let grant_ids_by_data_id = LookupMap::new(StorageKey::GrantIdsByDataId);
+        let grant_ids_by_label = LookupMap::new(StorageKey::GrantIdsByLabel);
+        let attested_grant_ids = LookupSet::new(StorageKey::AttestedGrantIds);
+        let default_timelock_nanos_by_owner =
+            LookupMap::new(StorageKey::DefaultTimelockNanosByOwner);
+        let idempotency_keys = LookupMap::new(StorageKey::IdempotencyEntries);
+        let idempotency_queue = LookupMap::new(StorageKey::IdempotencyQueue);
+        let frozen_owners = LookupSet::new(StorageKey::FrozenOwners);
+        let data_ids = Vector::new(StorageKey::DataIds);
+        let data_id_positions = LookupMap::new(StorageKey::DataIdPositions);
+        let data_id_claims = LookupMap::new(StorageKey::DataIdClaims);
+        let audit_log = Vector::new(StorageKey::AuditLog);
+        let claim_delegates = LookupSet::new(StorageKey::ClaimDelegates);
+        let used_consent_nonces = LookupSet::new(StorageKey::UsedConsentNonces);
+        let counted_grantees = LookupSet::new(StorageKey::CountedGrantees);
 
         Self {
             grants_by_id,
             grant_ids_by_owner,
             grant_ids_by_grantee,
             grant_ids_by_data_id,
+            grant_ids_by_label,
+            attested_grant_ids,
+            default_timelock_nanos_by_owner,
+            idempotency_keys,
+            idempotency_queue,
+            frozen_owners,
+            data_ids,
+            data_id_positions,
+            data_id_claims,
+            audit_log,
+            claim_delegates,
+            used_consent_nonces,
+            counted_grantees,
+            reject_self_grant: false,
+            timelock_override_enabled: false,
+            pause: PauseStatus::default(),
+            maintenance_message: None,
+            data_id_claims_enabled: false,
+            distinct_grantee_count: 0,
+            audit_log_enabled: false,
+            strict_locked_until: false,
+            idempotency_queue_head: 0,
+            idempotency_queue_tail: 0,
         }
     }
 }
@@ -139,22 +715,210 @@ pub fn public_key_bytes_ref(public_key: &PublicKey) -> &[u8; 32] {
     u8_to_fixed_length_array!(&public_key.as_bytes()[1..])
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum AuditAction {
+    Insert,
+    Delete,
+}
+
+// The insert an idempotency key was first used for. Retrying with the key returns `grant_id`, but only for
+// the same grantee and data_id.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct IdempotencyEntry {
+    grant_id: String,
+    first_used_at: EpochHeight,
+    grantee: PublicKey,
+    data_id: String,
+}
+
+// One line of the on-chain audit log, see `set_audit_log`.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuditEntry {
+    action: AuditAction,
+    grant_id: String,
+    actor: AccountId,
+    block_height: BlockHeight,
+}
+
+// Where an event came from, flattened into every event's data.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventTrace {
+    block_height: BlockHeight,
+    predecessor: AccountId,
+}
+
+impl EventTrace {
+    fn now() -> Self {
+        Self {
+            block_height: env::block_height(),
+            predecessor: env::predecessor_account_id(),
+        }
+    }
+}
+
+// Every event shares one schema version, so indexers only have to route on one number.
+// Bump minor for additive payload changes and major for anything else, on all events at once,
+// and record the change in the README's event schema table.
 #[near_bindgen(event_json(standard = "FractalRegistry"))]
 pub enum FractalRegistryEvents {
-    #[event_version("0")]
+    #[event_version("2.7.0")]
     GrantInserted {
         owner: PublicKey,
         grantee: PublicKey,
         data_id: String,
         locked_until: EpochHeight,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sponsor: Option<AccountId>,
+        #[serde(flatten)]
+        trace: EventTrace,
     },
 
-    #[event_version("0")]
+    #[event_version("2.7.0")]
     GrantDeleted {
         owner: PublicKey,
         grantee: PublicKey,
         data_id: String,
         locked_until: EpochHeight,
+        grant_id: String,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    GrantExpired {
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: EpochHeight,
+        expires_at: EpochHeight,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    GrantTimelockOverridden {
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: EpochHeight,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    GranteeRotated {
+        owner: PublicKey,
+        old_grantee: PublicKey,
+        new_grantee: PublicKey,
+        data_id: String,
+        locked_until: EpochHeight,
+        old_grant_id: String,
+        new_grant_id: String,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    GrantAttested {
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: EpochHeight,
+        attestor: AccountId,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    OwnerFrozen {
+        owner: PublicKey,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    OwnerUnfrozen {
+        owner: PublicKey,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    GrantTimelockSettled {
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        old_locked_until: EpochHeight,
+        old_grant_id: String,
+        new_grant_id: String,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    OrphansSwept {
+        owner: PublicKey,
+        count: u64,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    GrantsPruned {
+        owner: PublicKey,
+        count: u64,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    OwnerIndexRebuilt {
+        owner: PublicKey,
+        count: u64,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    GrantReassigned {
+        owner: PublicKey,
+        old_grantee: PublicKey,
+        new_grantee: PublicKey,
+        data_id: String,
+        locked_until: EpochHeight,
+        old_grant_id: String,
+        new_grant_id: String,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    GrantRevokedWithConsent {
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: EpochHeight,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    ClaimDelegateAdded {
+        claimant: PublicKey,
+        delegate: PublicKey,
+        #[serde(flatten)]
+        trace: EventTrace,
+    },
+
+    #[event_version("2.7.0")]
+    ClaimDelegateRemoved {
+        claimant: PublicKey,
+        delegate: PublicKey,
+        #[serde(flatten)]
+        trace: EventTrace,
     },
 }
 
@@ -165,16 +929,14 @@ fn nep413_verify(
     signature: Vec<u8>,
     owner: &PublicKey,
 ) {
-    require!(
-        owner.curve_type() == CurveType::ED25519,
-        "Only ed25519 keys are supported",
-    );
+    require!(owner.curve_type() == CurveType::ED25519, ERR_ONLY_ED25519,);
 
     // Serde didn't have [u8; 64] implemented, only up to 32. So, I've decided to convert them inside the function.
     let nonce: [u8; 32] = *u8_to_fixed_length_array!(nonce.as_slice());
     let signature: [u8; 64] = *u8_to_fixed_length_array!(signature.as_slice());
 
 
+    #[allow(clippy::redundant_field_names)]
     let hashed_payload = nep413_hashed_payload(&Nep413Payload {
         message: message,
         nonce,
@@ -184,129 +946,863 @@ fn nep413_verify(
 
     require!(
         env::ed25519_verify(&signature, &hashed_payload, public_key_bytes_ref(owner),),
-        "Signature doesn't match"
+        ERR_SIGNATURE_MISMATCH
+    );
+}
+
+// `PublicKey` only parses the curves below today, but we don't want grants stored under keys we
+// can't verify later if that ever changes, so the curve byte is checked explicitly.
+fn check_supported_curve(public_key: &PublicKey) -> Result<(), RegistryError> {
+    let supported = [CurveType::ED25519 as u8, CurveType::SECP256K1 as u8];
+
+    match public_key.as_bytes().first() {
+        Some(curve) if supported.contains(curve) => Ok(()),
+        _ => Err(RegistryError::UnsupportedKeyCurve),
+    }
+}
+
+fn require_supported_curve(public_key: &PublicKey) {
+    check_supported_curve(public_key).unwrap_or_else(|error| error.panic())
+}
+
+// NEAR charges every stored key/value record this many bytes on top of its key and value.
+const STORAGE_BYTES_PER_RECORD: u64 = 40;
+// NEAR's limit on account id length.
+const MAX_ACCOUNT_ID_LEN: usize = 64;
+
+fn audit_entry(action: AuditAction, grant_id: String) -> AuditEntry {
+    AuditEntry {
+        action,
+        grant_id,
+        actor: env::predecessor_account_id(),
+        block_height: env::block_height(),
+    }
+}
+
+fn borsh_len<T: BorshSerialize>(value: &T) -> u64 {
+    borsh::to_vec(value).unwrap().len() as u64
+}
+
+// How much pushing a grant_id of `grant_id_len` bytes onto `index[key]` adds to storage.
+fn index_push_len<K>(index: &LookupMap<K, Vec<String>>, key: &K, grant_id_len: u64) -> u64
+where
+    K: BorshSerialize + Ord + Clone,
+{
+    if index.contains_key(key) {
+        grant_id_len
+    } else {
+        let prefix_len = 1;
+        let new_vec_len = borsh_len(&Vec::<String>::new());
+
+        STORAGE_BYTES_PER_RECORD + prefix_len + borsh_len(key) + new_vec_len + grant_id_len
+    }
+}
+
+// The timestamp `duration_nanos` from now. Durations that would overflow are rejected rather than saturated
+// or wrapped, so a huge lock can't quietly turn into an odd (or already passed) timestamp.
+fn nanos_from_now(duration_nanos: u64) -> Result<EpochHeight, RegistryError> {
+    env::block_timestamp()
+        .checked_add(duration_nanos)
+        .ok_or(RegistryError::DurationTooLarge)
+}
+
+fn seconds_from_now(duration_seconds: u64) -> Result<EpochHeight, RegistryError> {
+    duration_seconds
+        .checked_mul(1_000_000_000)
+        .ok_or(RegistryError::DurationTooLarge)
+        .and_then(nanos_from_now)
+}
+
+// With the `gas-profiling` feature, the profiled methods end by logging `GAS_PROFILE: <method> <gas used>`.
+// That's a plain log, not an event, so indexers ignore it; deployed builds shouldn't enable it at all.
+// Only change methods are profiled: views like `find_grants` can't read `used_gas` (it panics with
+// ProhibitedInView), so their cost has to come from the gas burnt calling them as a transaction.
+#[cfg(feature = "gas-profiling")]
+fn log_gas_used(method: &str) {
+    env::log_str(&format!(
+        "GAS_PROFILE: {} {}",
+        method,
+        env::used_gas().as_gas()
+    ));
+}
+
+#[cfg(not(feature = "gas-profiling"))]
+fn log_gas_used(_method: &str) {}
+
+// Every method taking a list of items caps it at this, so a call fails up front instead of running out of gas halfway.
+const MAX_BATCH_SIZE: usize = 32;
+
+fn require_batch_size(len: usize) {
+    require!(len <= MAX_BATCH_SIZE, ERR_BATCH_TOO_LARGE);
+}
+
+// Unpaginated searches matching more grants than this fail with an error pointing at pagination,
+// rather than running out of gas while serializing the result.
+const MAX_RESULTS: usize = 100;
+
+const DASHBOARD_SAMPLE_SIZE: usize = 5;
+
+fn require_paginated_past_max_results(len: usize) {
+    require!(len <= MAX_RESULTS, ERR_TOO_MANY_RESULTS);
+}
+
+fn require_limit(limit: u64) {
+    require!(limit <= MAX_RESULTS as u64, ERR_LIMIT_TOO_LARGE);
+}
+
+// How many items to skip for `from_index`, without truncating it on 32-bit targets like wasm32.
+fn skip_len(from_index: u64) -> usize {
+    usize::try_from(from_index).unwrap_or(usize::MAX)
+}
+
+// Admin actions can only be taken by the contract account itself.
+fn require_admin() {
+    require!(
+        env::predecessor_account_id() == env::current_account_id(),
+        ERR_NOT_CONTRACT_ACCOUNT
     );
 }
 
 #[near_bindgen]
 impl FractalRegistry {
-    pub fn grant_message_recipient(&self) -> String {
-        "idos.network".into()
+    // Call once right after deploying over the legacy state layout.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let legacy: LegacyFractalRegistry = env::state_read().expect(ERR_NO_STATE_TO_MIGRATE);
+
+        Self {
+            grants_by_id: legacy.grants_by_id,
+            grant_ids_by_owner: legacy.grant_ids_by_owner,
+            grant_ids_by_grantee: legacy.grant_ids_by_grantee,
+            grant_ids_by_data_id: legacy.grant_ids_by_data_id,
+            ..Self::default()
+        }
     }
 
-    pub fn insert_grant(
-        &mut self,
-        grantee: PublicKey,
-        data_id: String,
-        locked_until: Option<EpochHeight>,
-    ) {
-        self._insert_grant(env::signer_account_pk(), grantee, data_id, locked_until)
+    // Bytes of state the contract currently uses, which its balance must cover for storage staking.
+    pub fn storage_usage(&self) -> u64 {
+        env::storage_usage()
     }
 
-    pub fn insert_grant_by_signature_message(
+    // How much storage `insert_grant(grantee, data_id)` would use if `owner` sent it now, and what staking that
+    // costs. It counts exactly what gets written, so it only drifts if state changes before the insert.
+    // `sender` is the account the insert will come from, which only matters with the audit log on; leaving it
+    // out sizes the audit entry for the longest possible account id, so the estimate can only come out high.
+    pub fn estimate_insert_cost(
         &self,
         owner: PublicKey,
         grantee: PublicKey,
         data_id: String,
-        locked_until: Option<EpochHeight>,
-    ) -> String {
-        format!(
-            "operation: insertGrant\n\
-            owner: {}\n\
-            grantee: {}\n\
-            dataId: {}\n\
-            lockedUntil: {}",
-            Into::<String>::into(&owner),
-            Into::<String>::into(&grantee),
+        sender: Option<AccountId>,
+    ) -> InsertCostEstimate {
+        let grant = Grant {
+            owner,
+            grantee,
             data_id,
-            locked_until.unwrap_or(0)
-        )
+            locked_until: 0,
+            expires_at: None,
+            expired: false,
+            label: None,
+            required_attestor: None,
+            created_at: Some(env::block_timestamp()),
+            valid_from: None,
+            immutable: false,
+            co_owners: vec![],
+            sponsor: None,
+        };
+        let grant_id = derive_grant_id(&grant);
+        let grant_id_len = borsh_len(&grant_id);
+        let prefix_len = 1;
+
+        let storage_bytes = STORAGE_BYTES_PER_RECORD
+            + prefix_len
+            + grant_id_len
+            + borsh_len(&grant)
+            + index_push_len(&self.grant_ids_by_owner, &grant.owner, grant_id_len)
+            + index_push_len(&self.grant_ids_by_grantee, &grant.grantee, grant_id_len)
+            + index_push_len(&self.grant_ids_by_data_id, &grant.data_id, grant_id_len)
+            + self.track_data_id_len(&grant.data_id)
+            + self.counted_grantee_len(&grant.grantee)
+            + self.claim_len(&grant.owner, &grant.data_id)
+            + self.audit_len(AuditAction::Insert, grant_id, sender);
+
+        InsertCostEstimate {
+            storage_bytes,
+            storage_cost: env::storage_byte_cost().saturating_mul(storage_bytes as u128),
+        }
     }
 
-    pub fn insert_grant_by_signature(
-        &mut self,
-        owner: PublicKey,
-        grantee: PublicKey,
-        data_id: String,
-        locked_until: Option<EpochHeight>,
-        nonce: Vec<u8>,
-        signature: Vec<u8>,
-    ) {
-        nep413_verify(
-            self.insert_grant_by_signature_message(
-                owner.clone(),
-                grantee.clone(),
-                data_id.clone(),
-                locked_until,
-            ),
-            nonce,
-            self.grant_message_recipient(),
-            signature,
-            &owner,
-        );
+    // A new data_id takes one `data_ids` element and one `data_id_positions` entry, each keyed or valued
+    // by a u32 position.
+    fn track_data_id_len(&self, data_id: &String) -> u64 {
+        if self.data_id_positions.contains_key(data_id) {
+            return 0;
+        }
 
-        self._insert_grant(owner, grantee, data_id, locked_until)
+        let prefix_len = 1;
+        let position_len = borsh_len(&0u32);
+
+        2 * (STORAGE_BYTES_PER_RECORD + prefix_len + position_len + borsh_len(data_id))
     }
 
-    fn _insert_grant(
+    // A grantee not yet counted in `distinct_grantees` gets a `counted_grantees` entry, which has no value.
+    fn counted_grantee_len(&self, grantee: &PublicKey) -> u64 {
+        if self.counted_grantees.contains(grantee) {
+            return 0;
+        }
+
+        let prefix_len = 1;
+
+        STORAGE_BYTES_PER_RECORD + prefix_len + borsh_len(grantee)
+    }
+
+    // With claims on, the first grant on a data_id also writes its `data_id_claims` entry.
+    fn claim_len(&self, owner: &PublicKey, data_id: &String) -> u64 {
+        if !self.data_id_claims_enabled || self.data_id_claims.contains_key(data_id) {
+            return 0;
+        }
+
+        let prefix_len = 1;
+
+        STORAGE_BYTES_PER_RECORD + prefix_len + borsh_len(data_id) + borsh_len(owner)
+    }
+
+    fn audit_len(&self, action: AuditAction, grant_id: String, actor: Option<AccountId>) -> u64 {
+        if !self.audit_log_enabled {
+            return 0;
+        }
+
+        let prefix_len = 1;
+        let index_len = borsh_len(&0u32);
+        let entry = AuditEntry {
+            action,
+            grant_id,
+            actor: actor.unwrap_or_else(|| "a".repeat(MAX_ACCOUNT_ID_LEN).parse().unwrap()),
+            block_height: env::block_height(),
+        };
+
+        STORAGE_BYTES_PER_RECORD + prefix_len + index_len + borsh_len(&entry)
+    }
+
+    // Self-grants are almost always a client bug, but rejecting them is opt-in to keep existing callers working.
+    pub fn set_reject_self_grant(&mut self, enabled: bool) {
+        require_admin();
+
+        self.reject_self_grant = enabled;
+    }
+
+    // Off by default, since existing callers may rely on passing small values (see `MAX_LOCKED_UNTIL_AGE`).
+    pub fn set_strict_locked_until(&mut self, enabled: bool) {
+        require_admin();
+
+        self.strict_locked_until = enabled;
+    }
+
+    // Emergency escape hatch: while enabled, owners can delete their grants even if they're timelocked.
+    // Each such deletion also emits `grant_timelock_overridden`.
+    pub fn set_timelock_override(&mut self, enabled: bool) {
+        require_admin();
+
+        self.timelock_override_enabled = enabled;
+    }
+
+    // Grants the signer inserts without a locked_until get locked for this long from the moment they're inserted.
+    // None goes back to not locking them.
+    pub fn set_default_timelock(&mut self, default_timelock_nanos: Option<u64>) {
+        let owner = env::signer_account_pk();
+
+        match default_timelock_nanos {
+            Some(nanos) => self.default_timelock_nanos_by_owner.insert(owner, nanos),
+            None => self.default_timelock_nanos_by_owner.remove(&owner),
+        };
+    }
+
+    // While paused, grants can't be inserted, deleted or attested. Views keep working.
+    pub fn set_paused(&mut self, paused: bool) {
+        require_admin();
+
+        self.pause = PauseStatus {
+            paused,
+            changed_by: Some(env::predecessor_account_id()),
+            changed_at: Some(env::block_height()),
+        };
+    }
+
+    pub fn pause_status(&self) -> PauseStatus {
+        self.pause.clone()
+    }
+
+    pub fn get_config(&self) -> RegistryConfig {
+        RegistryConfig {
+            max_batch_size: MAX_BATCH_SIZE as u64,
+            max_results: MAX_RESULTS as u64,
+            max_data_id_len: MAX_DATA_ID_LEN as u64,
+            max_label_len: MAX_LABEL_LEN as u64,
+            max_idempotency_key_len: MAX_IDEMPOTENCY_KEY_LEN as u64,
+            idempotency_key_ttl: IDEMPOTENCY_KEY_TTL,
+            max_co_owners: MAX_CO_OWNERS as u64,
+            dashboard_sample_size: DASHBOARD_SAMPLE_SIZE as u64,
+            reject_self_grant: self.reject_self_grant,
+            strict_locked_until: self.strict_locked_until,
+            timelock_override_enabled: self.timelock_override_enabled,
+            data_id_claims_enabled: self.data_id_claims_enabled,
+            audit_log_enabled: self.audit_log_enabled,
+            paused: self.pause.paused,
+            maintenance_message: self.maintenance_message.clone(),
+        }
+    }
+
+    // Blocks the same writes as pausing, but rejects them with `message` (or a generic one), and stays on
+    // until it's explicitly turned off. Meant for migrations rather than incidents.
+    pub fn set_maintenance(&mut self, enabled: bool, message: Option<String>) {
+        require_admin();
+
+        self.maintenance_message = if enabled {
+            Some(message.unwrap_or_else(|| ERR_MAINTENANCE.into()))
+        } else {
+            None
+        };
+    }
+
+    fn check_writable(&self) -> Result<(), RegistryError> {
+        if let Some(message) = &self.maintenance_message {
+            return Err(RegistryError::Maintenance(message.clone()));
+        }
+        if self.pause.paused {
+            return Err(RegistryError::Paused);
+        }
+
+        Ok(())
+    }
+
+    fn require_writable(&self) {
+        self.check_writable().unwrap_or_else(|error| error.panic())
+    }
+
+    // Lets owners who suspect their key is compromised stop every change to their grants (inserts, deletes,
+    // rotations, purges, claim delegates) until they unfreeze. Only the owner can do either.
+    pub fn freeze_own_grants(&mut self) {
+        let owner = env::signer_account_pk();
+
+        if self.frozen_owners.insert(owner.clone()) {
+            FractalRegistryEvents::OwnerFrozen {
+                owner,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+    }
+
+    pub fn unfreeze_own_grants(&mut self) {
+        let owner = env::signer_account_pk();
+
+        if self.frozen_owners.remove(&owner) {
+            FractalRegistryEvents::OwnerUnfrozen {
+                owner,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+    }
+
+    fn check_not_frozen(&self, owner: &PublicKey) -> Result<(), RegistryError> {
+        if self.frozen_owners.contains(owner) {
+            return Err(RegistryError::OwnerFrozen);
+        }
+
+        Ok(())
+    }
+
+    fn require_not_frozen(&self, owner: &PublicKey) {
+        self.check_not_frozen(owner)
+            .unwrap_or_else(|error| error.panic())
+    }
+
+    // Repairs the owner's index if it ever points at grants that no longer exist, dropping up to `limit` of
+    // those ids. Returns (and announces with `orphans_swept`) how many were dropped.
+    pub fn sweep_orphans(&mut self, owner: PublicKey, limit: u64) -> u64 {
+        require_admin();
+        self.require_writable();
+
+        let Some(grant_ids) = self.grant_ids_by_owner.get(&owner) else {
+            return 0;
+        };
+        let orphans: Vec<String> = grant_ids
+            .iter()
+            .filter(|id| !self.grants_by_id.contains_key(*id))
+            .take(limit as usize)
+            .cloned()
+            .collect();
+
+        if orphans.is_empty() {
+            return 0;
+        }
+
+        self.grant_ids_by_owner
+            .get_mut(&owner)
+            .unwrap()
+            .retain(|id| !orphans.contains(id));
+
+        let count = orphans.len() as u64;
+        FractalRegistryEvents::OrphansSwept {
+            owner,
+            count,
+            trace: EventTrace::now(),
+        }
+        .emit();
+
+        count
+    }
+
+    // For owners whose keys are gone for good. Unlike deletes, this ignores timelocks and freezing, and drops
+    // the owner's data_id claims once they have no grants left on them. Immutable grants stay, as they do
+    // everywhere: they're skipped and counted in `immutable`, and don't count towards `limit`.
+    pub fn prune_grants_for_owner(&mut self, owner: PublicKey, limit: u64) -> PruneReport {
+        require_admin();
+        self.require_writable();
+
+        let mut immutable = 0;
+        let mut grants: Vec<Grant> = vec![];
+        for id in self.grant_ids_by_owner.get(&owner).unwrap_or(&vec![]) {
+            if grants.len() as u64 >= limit {
+                break;
+            }
+            match self.grants_by_id.get(id) {
+                Some(grant) if grant.immutable => immutable += 1,
+                Some(grant) => grants.push(grant.clone()),
+                None => {}
+            }
+        }
+
+        for grant in &grants {
+            self._unindex_grant(grant);
+        }
+        for grant in &grants {
+            if self.data_id_claims.get(&grant.data_id) == Some(&owner)
+                && self
+                    ._find_grant_ids(Some(owner.clone()), None, Some(grant.data_id.clone()))
+                    .is_empty()
+            {
+                self.data_id_claims.remove(&grant.data_id);
+            }
+        }
+
+        let count = grants.len() as u64;
+        if count > 0 {
+            FractalRegistryEvents::GrantsPruned {
+                owner,
+                count,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+
+        PruneReport {
+            pruned: count,
+            immutable,
+        }
+    }
+
+    // Read-only drift check of the owner's index, cheaper than finding out through `rebuild_owner_index`.
+    pub fn check_owner_consistency(&self, owner: PublicKey) -> ConsistencyReport {
+        let empty = vec![];
+        let grant_ids = self.grant_ids_by_owner.get(&owner).unwrap_or(&empty);
+
+        let mut report = ConsistencyReport {
+            indexed: grant_ids.len() as u64,
+            dangling: 0,
+            mismatched: 0,
+        };
+        for id in grant_ids {
+            match self.grants_by_id.get(id) {
+                None => report.dangling += 1,
+                Some(grant) if grant.owner != owner => report.mismatched += 1,
+                Some(_) => {}
+            }
+        }
+
+        report
+    }
+
+    // Recovery for a drifted owner index: appends the owner's grants it's missing, found through `limit` (at
+    // most `MAX_BATCH_SIZE`) data_ids of `all_data_ids` from `from_index`, and keeps the ids already there, in
+    // their order. Restored ids go at the end, as if just inserted. Grants on data_ids that `all_data_ids`
+    // doesn't list can't be found, so upgraded contracts need `backfill_data_ids` first.
+    pub fn rebuild_owner_index(
         &mut self,
         owner: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> IndexRebuild {
+        require_admin();
+        self.require_writable();
+        require!(limit <= MAX_BATCH_SIZE as u64, ERR_BATCH_TOO_LARGE);
+
+        let mut grant_ids = self
+            .grant_ids_by_owner
+            .get(&owner)
+            .cloned()
+            .unwrap_or_default();
+        let mut indexed: std::collections::HashSet<String> = grant_ids.iter().cloned().collect();
+        let already_indexed = grant_ids.len();
+        let end = from_index
+            .saturating_add(limit)
+            .min(self.data_ids.len() as u64);
+
+        for index in from_index..end {
+            let data_id = self.data_ids.get(index as u32).unwrap();
+            for id in self.grant_ids_by_data_id.get(data_id).unwrap_or(&vec![]) {
+                let owned = self
+                    .grants_by_id
+                    .get(id)
+                    .map_or(false, |grant| grant.owner == owner);
+                if owned && indexed.insert(id.clone()) {
+                    grant_ids.push(id.clone());
+                }
+            }
+        }
+
+        let added = (grant_ids.len() - already_indexed) as u64;
+        let count = grant_ids.len() as u64;
+        if added > 0 {
+            self.grant_ids_by_owner.insert(owner.clone(), grant_ids);
+        }
+
+        FractalRegistryEvents::OwnerIndexRebuilt {
+            owner,
+            count,
+            trace: EventTrace::now(),
+        }
+        .emit();
+
+        IndexRebuild {
+            added,
+            next_index: (end < self.data_ids.len() as u64).then_some(end),
+        }
+    }
+
+    // While enabled, every grant insert and delete (including the ones inside rotations, reassignments and
+    // prunes) appends an entry to `audit_log`. Entries are never removed, so the contract pays storage for the
+    // log's whole history; archive it off-chain through `audit_log` if it has to be kept beyond that.
+    pub fn set_audit_log(&mut self, enabled: bool) {
+        require_admin();
+
+        self.audit_log_enabled = enabled;
+    }
+
+    // Oldest first, `limit` (at most `MAX_RESULTS`) at a time. Turning the log off and on again leaves a gap
+    // rather than starting a new log.
+    pub fn audit_log(&self, from_index: u64, limit: u64) -> Vec<AuditEntry> {
+        require_limit(limit);
+
+        self.audit_log
+            .iter()
+            .skip(skip_len(from_index))
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    // The raw index vectors, for comparing what's indexed with what's in grants_by_id. They may hold
+    // ids of grants that no longer exist (see `sweep_orphans`).
+    // `require_admin` reads the predecessor, which views don't have, so these only work when the contract
+    // account calls them as a transaction (`near call`, not `near view`). That doesn't make the indexes
+    // private: anyone can read the contract's raw state over RPC (`view_state`).
+    pub fn debug_index_for_owner(&self, owner: PublicKey) -> Vec<String> {
+        require_admin();
+
+        self.grant_ids_by_owner
+            .get(&owner)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn debug_index_for_grantee(&self, grantee: PublicKey) -> Vec<String> {
+        require_admin();
+
+        self.grant_ids_by_grantee
+            .get(&grantee)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn debug_index_for_data_id(&self, data_id: String) -> Vec<String> {
+        require_admin();
+
+        self.grant_ids_by_data_id
+            .get(&data_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // While enabled, the first owner to grant on a data_id claims it, and other owners can't grant on it.
+    // Claims made while enabled stay when it's turned off, but aren't enforced until it's back on.
+    pub fn set_data_id_claims(&mut self, enabled: bool) {
+        require_admin();
+
+        self.data_id_claims_enabled = enabled;
+    }
+
+    // Deleting grants doesn't release the claim, this does. While other owners still have grants on the
+    // data_id, nobody can claim it again.
+    pub fn clear_data_id_claim(&mut self, data_id: String) {
+        require_admin();
+
+        self.data_id_claims.remove(&data_id);
+    }
+
+    pub fn data_id_claimant(&self, data_id: String) -> Option<PublicKey> {
+        self.data_id_claims.get(&data_id).cloned()
+    }
+
+    // Lets `delegate` grant on every data_id the signer has claimed (see `set_data_id_claims`), now or later.
+    // The delegate's grants stay its own, and a data_id it grants on first is claimed by the delegate.
+    pub fn add_claim_delegate(&mut self, delegate: PublicKey) {
+        self.require_writable();
+        let claimant = env::signer_account_pk();
+        self.require_not_frozen(&claimant);
+
+        if self
+            .claim_delegates
+            .insert((claimant.clone(), delegate.clone()))
+        {
+            FractalRegistryEvents::ClaimDelegateAdded {
+                claimant,
+                delegate,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+    }
+
+    pub fn remove_claim_delegate(&mut self, delegate: PublicKey) {
+        self.require_writable();
+        let claimant = env::signer_account_pk();
+        self.require_not_frozen(&claimant);
+
+        if self
+            .claim_delegates
+            .remove(&(claimant.clone(), delegate.clone()))
+        {
+            FractalRegistryEvents::ClaimDelegateRemoved {
+                claimant,
+                delegate,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+    }
+
+    pub fn is_claim_delegate(&self, claimant: PublicKey, delegate: PublicKey) -> bool {
+        self.claim_delegates.contains(&(claimant, delegate))
+    }
+
+    pub fn grant_message_recipient(&self) -> String {
+        "idos.network".into()
+    }
+
+    pub fn insert_grant(
+        &mut self,
         grantee: PublicKey,
         data_id: String,
         locked_until: Option<EpochHeight>,
     ) {
-        let grant = Grant {
-            owner: owner.clone(),
-            grantee: grantee.clone(),
-            data_id: data_id.clone(),
-            locked_until: locked_until.unwrap_or(0),
-        };
+        self._insert_grant(
+            env::signer_account_pk(),
+            grantee,
+            data_id,
+            locked_until,
+            GrantOptions::default(),
+        );
 
-        let grant_id = derive_grant_id(&grant);
+        log_gas_used("insert_grant");
+    }
 
-        require!(
-            !self.grants_by_id.contains_key(&grant_id),
-            "Grant already exists"
+    // Returns the grant exactly as stored, so callers don't need a follow-up read to confirm the write.
+    pub fn insert_and_get_grant(
+        &mut self,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+    ) -> Grant {
+        let grant_id = self._insert_grant(
+            env::signer_account_pk(),
+            grantee,
+            data_id,
+            locked_until,
+            GrantOptions::default(),
         );
 
-        self.grants_by_id.insert(grant_id.clone(), grant);
+        self.grants_by_id.get(&grant_id).unwrap().clone()
+    }
 
-        self.grant_ids_by_owner
-            .entry(owner.clone())
-            .or_default()
-            .push(grant_id.clone());
+    pub fn insert_grant_with_options(
+        &mut self,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+        options: GrantOptions,
+    ) -> String {
+        self._insert_grant(
+            env::signer_account_pk(),
+            grantee,
+            data_id,
+            locked_until,
+            options,
+        )
+    }
 
-        self.grant_ids_by_grantee
-            .entry(grantee.clone())
-            .or_default()
-            .push(grant_id.clone());
+    // Removes up to `limit` (at most `MAX_BATCH_SIZE`) expired idempotency keys, oldest first, and returns how
+    // many it removed. Anyone can call it: it only frees storage nothing reads anymore.
+    pub fn purge_expired_idempotency_keys(&mut self, limit: u64) -> u64 {
+        require!(limit <= MAX_BATCH_SIZE as u64, ERR_BATCH_TOO_LARGE);
 
-        self.grant_ids_by_data_id
-            .entry(data_id.clone())
-            .or_default()
-            .push(grant_id.clone());
+        self._purge_expired_idempotency_keys(limit)
+    }
 
-        let locked_until = locked_until.unwrap_or(0);
+    // Short-lived access: the grant expires `ttl_seconds` from now (see `GrantOptions::expires_at`).
+    pub fn insert_grant_expiring_in(
+        &mut self,
+        grantee: PublicKey,
+        data_id: String,
+        ttl_seconds: u64,
+    ) -> String {
+        let expires_at = seconds_from_now(ttl_seconds).unwrap_or_else(|error| error.panic());
 
-        FractalRegistryEvents::GrantInserted {
-            owner,
+        self._insert_grant(
+            env::signer_account_pk(),
             grantee,
             data_id,
-            locked_until,
+            None,
+            GrantOptions {
+                expires_at: Some(expires_at),
+                ..GrantOptions::default()
+            },
+        )
+    }
+
+    // The owner's unexpired grants, paginated, in the form `import_owner_grants` takes.
+    pub fn export_owner_grants(
+        &self,
+        owner: PublicKey,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<GrantInput> {
+        let grants: Vec<GrantInput> = self
+            ._find_grants(Some(owner), None, None)
+            .into_iter()
+            .filter(|grant| !grant.is_expired())
+            .skip(skip_len(from_index.unwrap_or(0)))
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(|grant| GrantInput {
+                grantee: grant.grantee,
+                data_id: grant.data_id,
+                locked_until: grant.locked_until,
+                expires_at: grant.expires_at,
+                label: grant.label,
+                required_attestor: grant.required_attestor,
+                valid_from: grant.valid_from,
+                immutable: grant.immutable,
+                co_owners: grant.co_owners,
+            })
+            .collect();
+        if limit.is_none() {
+            require_paginated_past_max_results(grants.len());
         }
-        .emit();
+
+        grants
     }
 
-    pub fn delete_grant(
+    // Inserts exported grants (at most `MAX_BATCH_SIZE` per call) as the signer's own, so they get new ids
+    // whenever the signer isn't the owner they were exported from. All-or-nothing, like `insert_grant_multi`.
+    // With `silent`, no grant_inserted is emitted, so event-based indexers never see these grants.
+    pub fn import_owner_grants(
+        &mut self,
+        grants: Vec<GrantInput>,
+        silent: Option<bool>,
+    ) -> Vec<String> {
+        require_batch_size(grants.len());
+        let owner = env::signer_account_pk();
+
+        grants
+            .into_iter()
+            .map(|grant| {
+                self._insert_grant(
+                    owner.clone(),
+                    grant.grantee,
+                    grant.data_id,
+                    Some(grant.locked_until),
+                    GrantOptions {
+                        expires_at: grant.expires_at,
+                        label: grant.label,
+                        required_attestor: grant.required_attestor,
+                        valid_from: grant.valid_from,
+                        immutable: grant.immutable,
+                        co_owners: grant.co_owners,
+                        silent: silent.unwrap_or(false),
+                        ..GrantOptions::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    // All-or-nothing: a data_id that's repeated in the batch (or already granted) fails the whole call with "Grant already exists".
+    // `silent` works as in `import_owner_grants`.
+    pub fn insert_grant_multi(
         &mut self,
         grantee: PublicKey,
+        data_ids: Vec<String>,
+        locked_until: Option<EpochHeight>,
+        silent: Option<bool>,
+    ) {
+        require_batch_size(data_ids.len());
+
+        let owner = env::signer_account_pk();
+
+        for data_id in data_ids {
+            self._insert_grant(
+                owner.clone(),
+                grantee.clone(),
+                data_id,
+                locked_until,
+                GrantOptions {
+                    silent: silent.unwrap_or(false),
+                    ..GrantOptions::default()
+                },
+            );
+        }
+    }
+
+    // `insert_grant_multi` the other way around: one data_id, many grantees, one grant_inserted each (none if
+    // `silent`). Also all-or-nothing, so a grantee that's repeated fails the whole call with "Grant already exists".
+    pub fn insert_grant_to_many(
+        &mut self,
+        grantees: Vec<PublicKey>,
         data_id: String,
         locked_until: Option<EpochHeight>,
+        silent: Option<bool>,
     ) {
-        self._delete_grant(env::signer_account_pk(), grantee, data_id, locked_until)
+        require_batch_size(grantees.len());
+
+        let owner = env::signer_account_pk();
+
+        for grantee in grantees {
+            self._insert_grant(
+                owner.clone(),
+                grantee,
+                data_id.clone(),
+                locked_until,
+                GrantOptions {
+                    silent: silent.unwrap_or(false),
+                    ..GrantOptions::default()
+                },
+            );
+        }
     }
 
-    pub fn delete_grant_by_signature_message(
+    pub fn insert_grant_by_signature_message(
         &self,
         owner: PublicKey,
         grantee: PublicKey,
@@ -314,7 +1810,7 @@ impl FractalRegistry {
         locked_until: Option<EpochHeight>,
     ) -> String {
         format!(
-            "operation: deleteGrant\n\
+            "operation: insertGrant\n\
             owner: {}\n\
             grantee: {}\n\
             dataId: {}\n\
@@ -326,7 +1822,7 @@ impl FractalRegistry {
         )
     }
 
-    pub fn delete_grant_by_signature(
+    pub fn insert_grant_by_signature(
         &mut self,
         owner: PublicKey,
         grantee: PublicKey,
@@ -336,7 +1832,7 @@ impl FractalRegistry {
         signature: Vec<u8>,
     ) {
         nep413_verify(
-            self.delete_grant_by_signature_message(
+            self.insert_grant_by_signature_message(
                 owner.clone(),
                 grantee.clone(),
                 data_id.clone(),
@@ -348,101 +1844,6191 @@ impl FractalRegistry {
             &owner,
         );
 
-        self._delete_grant(owner, grantee, data_id, locked_until)
-    }
-
-    fn _delete_grant(
+        self._insert_grant(
+            owner,
+            grantee,
+            data_id,
+            locked_until,
+            GrantOptions {
+                sponsor: Some(env::predecessor_account_id()),
+                ..GrantOptions::default()
+            },
+        );
+    }
+
+    fn _insert_grant(
         &mut self,
         owner: PublicKey,
         grantee: PublicKey,
         data_id: String,
         locked_until: Option<EpochHeight>,
-    ) {
-        self.find_grants(
-            Some(owner.clone()),
-            Some(grantee.clone()),
-            Some(data_id.clone()),
-        )
-        .iter()
-        .filter(|grant| match locked_until {
-            None => true,
-            Some(0) => true,
-            Some(locked_until_) => grant.locked_until == locked_until_,
-        })
-        .for_each(|grant| {
-            require!(
-                grant.locked_until < env::block_timestamp(),
-                "Grant is timelocked"
-            );
+        options: GrantOptions,
+    ) -> String {
+        self._try_insert_grant(owner, grantee, data_id, locked_until, options)
+            .unwrap_or_else(|error| error.panic())
+    }
 
-            let grant_id = derive_grant_id(grant);
+    // Nothing is written until every check has passed, so an Err leaves the state untouched.
+    fn _try_insert_grant(
+        &mut self,
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+        options: GrantOptions,
+    ) -> Result<String, RegistryError> {
+        let idempotency_key = match &options.idempotency_key {
+            Some(key) if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN => {
+                return Err(RegistryError::InvalidIdempotencyKey);
+            }
+            Some(key) => Some((owner.clone(), key.clone())),
+            None => None,
+        };
+        if let Some(key) = &idempotency_key {
+            self.check_writable()?;
+            self.check_not_frozen(&owner)?;
 
-            self.grants_by_id.remove(&grant_id);
+            if let Some(entry) = self.idempotency_keys.get(key) {
+                let fresh = env::block_timestamp()
+                    < entry.first_used_at.saturating_add(IDEMPOTENCY_KEY_TTL);
+                if fresh && (entry.grantee != grantee || entry.data_id != data_id) {
+                    return Err(RegistryError::IdempotencyKeyReused);
+                }
+                if fresh && self.grants_by_id.contains_key(&entry.grant_id) {
+                    return Ok(entry.grant_id.clone());
+                }
+            }
+        }
 
-            self.grant_ids_by_owner
-                .get_mut(&owner)
-                .unwrap_or(&mut vec![])
-                .retain(|id| *id != *grant_id);
+        check_supported_curve(&grantee)?;
+        if data_id.is_empty() {
+            return Err(RegistryError::EmptyDataId);
+        }
+        if data_id.len() > MAX_DATA_ID_LEN {
+            return Err(RegistryError::DataIdTooLong);
+        }
+        if self.reject_self_grant && owner == grantee {
+            return Err(RegistryError::SelfGrant);
+        }
+        if self.data_id_claims_enabled {
+            match self.data_id_claims.get(&data_id) {
+                Some(claimant) => {
+                    if *claimant != owner
+                        && !self
+                            .claim_delegates
+                            .contains(&(claimant.clone(), owner.clone()))
+                    {
+                        return Err(RegistryError::DataIdClaimed);
+                    }
+                }
+                // Unclaimed, but possibly granted on before claims were turned on: only an owner who has all
+                // of those grants gets to claim it, so nobody can claim a data_id out from under its users.
+                None => {
+                    if self._has_grants_by_others(&data_id, &owner) {
+                        return Err(RegistryError::DataIdClaimed);
+                    }
+                }
+            }
+        }
 
-            self.grant_ids_by_grantee
-                .get_mut(&grantee)
-                .unwrap_or(&mut vec![])
-                .retain(|id| *id != *grant_id);
+        let locked_until = match (
+            locked_until,
+            self.default_timelock_nanos_by_owner.get(&owner),
+        ) {
+            (Some(locked_until), _) => {
+                if self.strict_locked_until
+                    && locked_until != 0
+                    && locked_until < env::block_timestamp().saturating_sub(MAX_LOCKED_UNTIL_AGE)
+                {
+                    return Err(RegistryError::LockedUntilWrongUnit);
+                }
+                locked_until
+            }
+            (None, Some(nanos)) => nanos_from_now(*nanos)?,
+            (None, None) => 0,
+        };
 
-            self.grant_ids_by_data_id
-                .get_mut(&data_id)
-                .unwrap_or(&mut vec![])
-                .retain(|id| *id != *grant_id);
-        });
+        let grant = Grant {
+            owner: owner.clone(),
+            grantee: grantee.clone(),
+            data_id: data_id.clone(),
+            locked_until,
+            expires_at: options.expires_at,
+            expired: false,
+            label: options.label,
+            required_attestor: options.required_attestor,
+            created_at: Some(env::block_timestamp()),
+            valid_from: options.valid_from,
+            immutable: options.immutable,
+            co_owners: options.co_owners,
+            sponsor: options.sponsor,
+        };
 
-        let locked_until = locked_until.unwrap_or(0);
+        if grant.is_expired() {
+            return Err(RegistryError::AlreadyExpired);
+        }
+        if let Some(label) = &grant.label {
+            if label.is_empty() || label.len() > MAX_LABEL_LEN {
+                return Err(RegistryError::InvalidLabel);
+            }
+        }
+        if grant.co_owners.len() > MAX_CO_OWNERS {
+            return Err(RegistryError::TooManyCoOwners);
+        }
 
-        FractalRegistryEvents::GrantDeleted {
+        let sponsor = grant.sponsor.clone();
+        let grant_id = self._try_store_grant(grant)?;
+        if let Some(key) = idempotency_key {
+            self._remember_idempotency_key(key, grant_id.clone(), grantee.clone(), data_id.clone());
+        }
+        if self.data_id_claims_enabled && !self.data_id_claims.contains_key(&data_id) {
+            self.data_id_claims.insert(data_id.clone(), owner.clone());
+        }
+
+        if options.silent {
+            return Ok(grant_id);
+        }
+
+        FractalRegistryEvents::GrantInserted {
             owner,
             grantee,
             data_id,
             locked_until,
+            sponsor,
+            trace: EventTrace::now(),
         }
         .emit();
+
+        Ok(grant_id)
     }
 
-    pub fn grants_for(&self, grantee: PublicKey, data_id: String) -> Vec<Grant> {
-        self.find_grants(None, Some(grantee), Some(data_id))
+    fn _has_grants_by_others(&self, data_id: &String, owner: &PublicKey) -> bool {
+        self.grant_ids_by_data_id
+            .get(data_id)
+            .unwrap_or(&vec![])
+            .iter()
+            .filter_map(|id| self.grants_by_id.get(id))
+            .any(|grant| grant.owner != *owner)
     }
 
-    pub fn find_grants(
+    fn _remember_idempotency_key(
+        &mut self,
+        key: (PublicKey, String),
+        grant_id: String,
+        grantee: PublicKey,
+        data_id: String,
+    ) {
+        let now = env::block_timestamp();
+        self._purge_expired_idempotency_keys(IDEMPOTENCY_KEYS_PURGED_PER_INSERT);
+
+        self.idempotency_queue
+            .insert(self.idempotency_queue_tail, (key.clone(), now));
+        self.idempotency_queue_tail += 1;
+        self.idempotency_keys.insert(
+            key,
+            IdempotencyEntry {
+                grant_id,
+                first_used_at: now,
+                grantee,
+                data_id,
+            },
+        );
+    }
+
+    // Oldest first, stopping at the first key that hasn't expired yet.
+    fn _purge_expired_idempotency_keys(&mut self, limit: u64) -> u64 {
+        let mut purged = 0;
+
+        while purged < limit && self.idempotency_queue_head < self.idempotency_queue_tail {
+            let (key, first_used_at) = self
+                .idempotency_queue
+                .get(&self.idempotency_queue_head)
+                .unwrap()
+                .clone();
+            if env::block_timestamp() < first_used_at.saturating_add(IDEMPOTENCY_KEY_TTL) {
+                break;
+            }
+
+            // A key that expired and was used again has a newer entry, with its own place in the queue.
+            if self
+                .idempotency_keys
+                .get(&key)
+                .map_or(false, |entry| entry.first_used_at == first_used_at)
+            {
+                self.idempotency_keys.remove(&key);
+            }
+            self.idempotency_queue.remove(&self.idempotency_queue_head);
+            self.idempotency_queue_head += 1;
+            purged += 1;
+        }
+
+        purged
+    }
+
+    fn _store_grant(&mut self, grant: Grant) -> String {
+        self._try_store_grant(grant)
+            .unwrap_or_else(|error| error.panic())
+    }
+
+    fn _try_store_grant(&mut self, grant: Grant) -> Result<String, RegistryError> {
+        self.check_writable()?;
+        self.check_not_frozen(&grant.owner)?;
+
+        let grant_id = derive_grant_id(&grant);
+
+        if self.grants_by_id.contains_key(&grant_id) {
+            return Err(RegistryError::GrantAlreadyExists);
+        }
+
+        self.grant_ids_by_owner
+            .entry(grant.owner.clone())
+            .or_default()
+            .push(grant_id.clone());
+
+        self.grant_ids_by_grantee
+            .entry(grant.grantee.clone())
+            .or_default()
+            .push(grant_id.clone());
+        if self.counted_grantees.insert(grant.grantee.clone()) {
+            self.distinct_grantee_count += 1;
+        }
+
+        self.grant_ids_by_data_id
+            .entry(grant.data_id.clone())
+            .or_default()
+            .push(grant_id.clone());
+        self._track_data_id(&grant.data_id);
+
+        if let Some(label) = &grant.label {
+            self.grant_ids_by_label
+                .entry(label.clone())
+                .or_default()
+                .push(grant_id.clone());
+        }
+
+        self.grants_by_id.insert(grant_id.clone(), grant);
+        self._audit(AuditAction::Insert, &grant_id);
+
+        Ok(grant_id)
+    }
+
+    // Moves every grant the signer gave `old_grantee` over to `new_grantee`, keeping everything else about
+    // them (timelocks included, since the grantee doesn't lose anything). All-or-nothing: if `new_grantee`
+    // already has an identical grant, the call fails with "Grant already exists". Everything happens in one
+    // transaction, so owners with a very large number of grants to `old_grantee` can run out of gas.
+    pub fn rotate_grantee_key(&mut self, old_grantee: PublicKey, new_grantee: PublicKey) -> u64 {
+        require_supported_curve(&new_grantee);
+        require!(
+            !(self.reject_self_grant && new_grantee == env::signer_account_pk()),
+            ERR_SELF_GRANT
+        );
+
+        let grants = self._find_grants(Some(env::signer_account_pk()), Some(old_grantee), None);
+
+        for grant in &grants {
+            self._remove_grant(grant);
+
+            let rotated = Grant {
+                grantee: new_grantee.clone(),
+                ..grant.clone()
+            };
+            let new_grant_id = self._store_grant(rotated);
+
+            FractalRegistryEvents::GranteeRotated {
+                owner: grant.owner.clone(),
+                old_grantee: grant.grantee.clone(),
+                new_grantee: new_grantee.clone(),
+                data_id: grant.data_id.clone(),
+                locked_until: grant.locked_until,
+                old_grant_id: derive_grant_id(grant),
+                new_grant_id,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+
+        grants.len() as u64
+    }
+
+    // Gives one grant to `new_grantee` instead, keeping everything else about it. Unlike a key rotation, the
+    // old grantee loses access, so this is held to the same timelock rules as deleting. Returns the new grant_id.
+    pub fn reassign_grant(&mut self, grant_id: String, new_grantee: PublicKey) -> String {
+        require_supported_curve(&new_grantee);
+
+        let grant = self
+            .grants_by_id
+            .get(&grant_id)
+            .unwrap_or_else(|| env::panic_str(ERR_GRANT_DOES_NOT_EXIST))
+            .clone();
+
+        require!(
+            grant.owner == env::signer_account_pk(),
+            ERR_NOT_GRANT_OWNER_REASSIGN
+        );
+        require!(!grant.immutable, ERR_IMMUTABLE);
+        require!(self._is_deletable(&grant), ERR_TIMELOCKED);
+        require!(
+            !(self.reject_self_grant && new_grantee == grant.owner),
+            ERR_SELF_GRANT
+        );
+
+        self._revoke_grant(&grant);
+        let new_grant_id = self._store_grant(Grant {
+            grantee: new_grantee.clone(),
+            ..grant.clone()
+        });
+
+        FractalRegistryEvents::GrantReassigned {
+            owner: grant.owner,
+            old_grantee: grant.grantee,
+            new_grantee,
+            data_id: grant.data_id,
+            locked_until: grant.locked_until,
+            old_grant_id: grant_id,
+            new_grant_id: new_grant_id.clone(),
+            trace: EventTrace::now(),
+        }
+        .emit();
+
+        new_grant_id
+    }
+
+    // Once a grant's timelock has passed, replaces it with the same grant at `locked_until` 0, keeping its
+    // created_at, label and other settings. That changes its grant_id, which is returned. The grantee and
+    // required attestor stay the same, so an attestation carries over to the new grant_id.
+    pub fn settle_timelock(&mut self, grant_id: String) -> String {
+        let grant = self
+            .grants_by_id
+            .get(&grant_id)
+            .unwrap_or_else(|| env::panic_str(ERR_GRANT_DOES_NOT_EXIST))
+            .clone();
+
+        require!(
+            grant.owner == env::signer_account_pk(),
+            ERR_NOT_GRANT_OWNER_SETTLE
+        );
+        require!(grant.locked_until != 0, ERR_NO_TIMELOCK_TO_SETTLE);
+        require!(!grant.is_timelocked(), ERR_TIMELOCKED);
+
+        let attested = self.attested_grant_ids.contains(&grant_id);
+        self._remove_grant(&grant);
+        let settled_id = self._store_grant(Grant {
+            locked_until: 0,
+            ..grant.clone()
+        });
+        if attested {
+            self.attested_grant_ids.insert(settled_id.clone());
+        }
+
+        FractalRegistryEvents::GrantTimelockSettled {
+            owner: grant.owner,
+            grantee: grant.grantee,
+            data_id: grant.data_id,
+            old_locked_until: grant.locked_until,
+            old_grant_id: grant_id,
+            new_grant_id: settled_id.clone(),
+            trace: EventTrace::now(),
+        }
+        .emit();
+
+        settled_id
+    }
+
+    // Returns how many grants were deleted. When nothing matched, that's 0 and no event is emitted, so
+    // calling this again after a successful delete is harmless.
+    pub fn delete_grant(
+        &mut self,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+    ) -> u64 {
+        let deleted = self._delete_grant(
+            env::signer_account_pk(),
+            grantee,
+            data_id,
+            locked_until,
+            None,
+        );
+
+        log_gas_used("delete_grant");
+        deleted
+    }
+
+    // Like `delete_grant`, for a co-owner (see `GrantOptions::co_owners`) deleting `owner`'s grants.
+    // Only grants that list the signer as a co-owner are deleted; if there are none, this returns 0.
+    pub fn delete_grant_as_co_owner(
+        &mut self,
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+    ) -> u64 {
+        self._delete_grant(
+            owner,
+            grantee,
+            data_id,
+            locked_until,
+            Some(env::signer_account_pk()),
+        )
+    }
+
+    pub fn delete_grant_with_grantee_consent_message(
         &self,
-        owner: Option<PublicKey>,
-        grantee: Option<PublicKey>,
-        data_id: Option<String>,
-    ) -> Vec<Grant> {
-        let mut grant_id_searches = Vec::new();
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: EpochHeight,
+    ) -> String {
+        format!(
+            "operation: deleteGrantWithConsent\n\
+            owner: {}\n\
+            grantee: {}\n\
+            dataId: {}\n\
+            lockedUntil: {}",
+            Into::<String>::into(&owner),
+            Into::<String>::into(&grantee),
+            data_id,
+            locked_until
+        )
+    }
 
+    // Lets the signer delete their grant before its timelock has passed, given the grantee's NEP-413 signature
+    // over `delete_grant_with_grantee_consent_message`. The consent covers exactly that one grant (no wildcard
+    // locked_until), and nothing else about deleting changes: immutable grants and frozen owners still fail.
+    // Each consent works once: its nonce is recorded, so re-creating the same grant doesn't revive it.
+    // A grant that's still timelocked also gets a grant_revoked_with_consent, before its grant_deleted.
+    pub fn delete_grant_with_grantee_consent(
+        &mut self,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: EpochHeight,
+        nonce: Vec<u8>,
+        grantee_signature: Vec<u8>,
+    ) {
+        let owner = env::signer_account_pk();
+        let consent = (grantee.clone(), nonce.clone());
         require!(
-            owner.is_some() || grantee.is_some(),
-            "Required argument: `owner` and/or `grantee`",
+            !self.used_consent_nonces.contains(&consent),
+            ERR_CONSENT_USED
         );
 
-        let empty = vec![];
-        if let Some(owner) = owner {
-            grant_id_searches.push(self.grant_ids_by_owner.get(&owner).unwrap_or(&empty));
+        nep413_verify(
+            self.delete_grant_with_grantee_consent_message(
+                owner.clone(),
+                grantee.clone(),
+                data_id.clone(),
+                locked_until,
+            ),
+            nonce,
+            self.grant_message_recipient(),
+            grantee_signature,
+            &grantee,
+        );
+
+        let grant = self
+            ._find_grants(Some(owner), Some(grantee), Some(data_id))
+            .into_iter()
+            .find(|grant| grant.locked_until == locked_until)
+            .unwrap_or_else(|| env::panic_str(ERR_GRANT_DOES_NOT_EXIST));
+
+        if grant.is_timelocked() {
+            FractalRegistryEvents::GrantRevokedWithConsent {
+                owner: grant.owner.clone(),
+                grantee: grant.grantee.clone(),
+                data_id: grant.data_id.clone(),
+                locked_until: grant.locked_until,
+                trace: EventTrace::now(),
+            }
+            .emit();
         }
 
-        if let Some(grantee) = grantee {
-            grant_id_searches.push(self.grant_ids_by_grantee.get(&grantee).unwrap_or(&empty));
+        self._remove_grant(&grant);
+        self.used_consent_nonces.insert(consent);
+
+        FractalRegistryEvents::GrantDeleted {
+            grant_id: derive_grant_id(&grant),
+            owner: grant.owner,
+            grantee: grant.grantee,
+            data_id: grant.data_id,
+            locked_until: grant.locked_until,
+            trace: EventTrace::now(),
         }
+        .emit();
+    }
 
-        if let Some(data_id) = data_id {
-            grant_id_searches.push(self.grant_ids_by_data_id.get(&data_id).unwrap_or(&empty));
+    // Deletes exactly one grant, and only if it still looks like `expected`, so automated callers can't
+    // delete the wrong thing after a concurrent change. Unlike `delete_grant`, there are no wildcards.
+    pub fn delete_grant_checked(&mut self, grant_id: String, expected: GrantFields) {
+        self._try_delete_grant_checked(grant_id, expected)
+            .unwrap_or_else(|error| error.panic())
+    }
+
+    fn _try_delete_grant_checked(
+        &mut self,
+        grant_id: String,
+        expected: GrantFields,
+    ) -> Result<(), RegistryError> {
+        let grant = self
+            .grants_by_id
+            .get(&grant_id)
+            .ok_or(RegistryError::GrantDoesNotExist)?
+            .clone();
+
+        if !grant.is_owned_by(&env::signer_account_pk()) {
+            return Err(RegistryError::NotGrantOwner);
+        }
+        if !grant.matches(&expected) {
+            return Err(RegistryError::GrantMismatch);
+        }
+        if grant.immutable {
+            return Err(RegistryError::GrantIsImmutable);
         }
+        if !self._is_deletable(&grant) {
+            return Err(RegistryError::GrantIsTimelocked);
+        }
+        self.check_writable()?;
+        self.check_not_frozen(&grant.owner)?;
 
-        let Some((head, tail)) = grant_id_searches.split_first() else {
-            return vec![];
-        };
+        self._revoke_grant(&grant);
 
-        head.iter()
-            .filter(|id| tail.iter().all(|s| s.contains(id)))
-            .map(|id| self.grants_by_id.get(id).unwrap().clone())
-            .collect()
+        FractalRegistryEvents::GrantDeleted {
+            grant_id: derive_grant_id(&grant),
+            owner: grant.owner,
+            grantee: grant.grantee,
+            data_id: grant.data_id,
+            locked_until: grant.locked_until,
+            trace: EventTrace::now(),
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    pub fn delete_grant_by_signature_message(
+        &self,
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+    ) -> String {
+        format!(
+            "operation: deleteGrant\n\
+            owner: {}\n\
+            grantee: {}\n\
+            dataId: {}\n\
+            lockedUntil: {}",
+            Into::<String>::into(&owner),
+            Into::<String>::into(&grantee),
+            data_id,
+            locked_until.unwrap_or(0)
+        )
+    }
+
+    pub fn delete_grant_by_signature(
+        &mut self,
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+        nonce: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> u64 {
+        nep413_verify(
+            self.delete_grant_by_signature_message(
+                owner.clone(),
+                grantee.clone(),
+                data_id.clone(),
+                locked_until,
+            ),
+            nonce,
+            self.grant_message_recipient(),
+            signature,
+            &owner,
+        );
+
+        self._delete_grant(owner, grantee, data_id, locked_until, None)
+    }
+
+    fn _delete_grant(
+        &mut self,
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+        co_owner: Option<PublicKey>,
+    ) -> u64 {
+        self._try_delete_grant(owner, grantee, data_id, locked_until, co_owner)
+            .unwrap_or_else(|error| error.panic())
+    }
+
+    // Every matching grant is checked before any is deleted, so an Err leaves the state untouched.
+    fn _try_delete_grant(
+        &mut self,
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+        co_owner: Option<PublicKey>,
+    ) -> Result<u64, RegistryError> {
+        let grants: Vec<Grant> = self
+            ._find_grants(
+                Some(owner.clone()),
+                Some(grantee.clone()),
+                Some(data_id.clone()),
+            )
+            .into_iter()
+            .filter(|grant| match locked_until {
+                None => true,
+                Some(0) => true,
+                Some(locked_until_) => grant.locked_until == locked_until_,
+            })
+            .filter(|grant| {
+                co_owner
+                    .as_ref()
+                    .map_or(true, |co_owner| grant.co_owners.contains(co_owner))
+            })
+            .collect();
+
+        for grant in &grants {
+            if grant.immutable {
+                return Err(RegistryError::GrantIsImmutable);
+            }
+            if !self._is_deletable(grant) {
+                return Err(RegistryError::GrantIsTimelocked);
+            }
+            self.check_writable()?;
+            self.check_not_frozen(&grant.owner)?;
+        }
+
+        let deleted = grants.len() as u64;
+
+        // One event per deleted grant, with its own locked_until, not the (possibly wildcard) argument.
+        for grant in grants {
+            self._revoke_grant(&grant);
+
+            FractalRegistryEvents::GrantDeleted {
+                grant_id: derive_grant_id(&grant),
+                owner: grant.owner,
+                grantee: grant.grantee,
+                data_id: grant.data_id,
+                locked_until: grant.locked_until,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+
+        Ok(deleted)
+    }
+
+    // Bulk deletes (this and `revoke_all`) don't fail on timelocked grants like `delete_grant` does:
+    // those are skipped and left in place (unless the timelock override is on).
+    // They return how many grants were actually deleted, with one event per deleted grant.
+    pub fn delete_grants_for_data_id(&mut self, data_id: String) -> u64 {
+        let grants = self._find_grants(Some(env::signer_account_pk()), None, Some(data_id));
+
+        self._revoke_deletable_grants(grants)
+    }
+
+    // Every one of the signer's grants to `grantee` on `data_id`, whatever its `locked_until`.
+    pub fn revoke_all(&mut self, grantee: PublicKey, data_id: String) -> u64 {
+        let grants =
+            self._find_grants(Some(env::signer_account_pk()), Some(grantee), Some(data_id));
+
+        self._revoke_deletable_grants(grants)
+    }
+
+    fn _revoke_deletable_grants(&mut self, grants: Vec<Grant>) -> u64 {
+        let mut deleted = 0;
+
+        for grant in grants {
+            if !self._is_deletable(&grant) {
+                continue;
+            }
+
+            self._revoke_grant(&grant);
+            deleted += 1;
+
+            FractalRegistryEvents::GrantDeleted {
+                grant_id: derive_grant_id(&grant),
+                owner: grant.owner,
+                grantee: grant.grantee,
+                data_id: grant.data_id,
+                locked_until: grant.locked_until,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+
+        deleted
+    }
+
+    // Contracts can't act on a timer, so expiry is only announced when this runs: `grant_expired` is emitted
+    // the first time each of the signer's expired grants is seen here, and the grant is marked so it isn't
+    // announced again. Expired grants that aren't timelocked are then deleted (with `grant_deleted`); timelocked
+    // ones stay until a later purge after their lock passes.
+    // Looks at no more than `limit` expired grants and returns how many were deleted.
+    pub fn purge_expired_grants(&mut self, limit: u64) -> u64 {
+        let owner = env::signer_account_pk();
+        self.require_writable();
+        self.require_not_frozen(&owner);
+
+        let expired_ids: Vec<String> = self
+            .grant_ids_by_owner
+            .get(&owner)
+            .unwrap_or(&vec![])
+            .iter()
+            .filter(|id| self.grants_by_id.get(*id).map_or(false, Grant::is_expired))
+            .take(limit as usize)
+            .cloned()
+            .collect();
+
+        let mut deleted = 0;
+
+        for grant_id in expired_ids {
+            let grant = self.grants_by_id.get_mut(&grant_id).unwrap();
+
+            if !grant.expired {
+                grant.expired = true;
+
+                FractalRegistryEvents::GrantExpired {
+                    owner: grant.owner.clone(),
+                    grantee: grant.grantee.clone(),
+                    data_id: grant.data_id.clone(),
+                    locked_until: grant.locked_until,
+                    expires_at: grant.expires_at.unwrap(),
+                    trace: EventTrace::now(),
+                }
+                .emit();
+            }
+
+            let grant = grant.clone();
+            if grant.is_timelocked() || grant.immutable {
+                continue;
+            }
+
+            self._remove_grant(&grant);
+            deleted += 1;
+
+            FractalRegistryEvents::GrantDeleted {
+                grant_id: derive_grant_id(&grant),
+                owner: grant.owner,
+                grantee: grant.grantee,
+                data_id: grant.data_id,
+                locked_until: grant.locked_until,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+
+        deleted
+    }
+
+    fn _is_deletable(&self, grant: &Grant) -> bool {
+        !grant.immutable && (!grant.is_timelocked() || self.timelock_override_enabled)
+    }
+
+    // Owner-initiated removal, which only gets here for timelocked grants through the override.
+    fn _revoke_grant(&mut self, grant: &Grant) {
+        if grant.is_timelocked() {
+            FractalRegistryEvents::GrantTimelockOverridden {
+                owner: grant.owner.clone(),
+                grantee: grant.grantee.clone(),
+                data_id: grant.data_id.clone(),
+                locked_until: grant.locked_until,
+                trace: EventTrace::now(),
+            }
+            .emit();
+        }
+
+        self._remove_grant(grant);
+    }
+
+    // Attestations are dropped with the grant, so a grant moved to another grantee needs attesting again.
+    fn _remove_grant(&mut self, grant: &Grant) {
+        self.require_writable();
+        self.require_not_frozen(&grant.owner);
+        require!(!grant.immutable, ERR_IMMUTABLE);
+
+        self._unindex_grant(grant);
+    }
+
+    fn _unindex_grant(&mut self, grant: &Grant) {
+        let grant_id = derive_grant_id(grant);
+        self._audit(AuditAction::Delete, &grant_id);
+
+        self.grants_by_id.remove(&grant_id);
+        self.attested_grant_ids.remove(&grant_id);
+
+        self.grant_ids_by_owner
+            .get_mut(&grant.owner)
+            .unwrap_or(&mut vec![])
+            .retain(|id| *id != *grant_id);
+
+        if let Some(grantee_ids) = self.grant_ids_by_grantee.get_mut(&grant.grantee) {
+            grantee_ids.retain(|id| *id != *grant_id);
+            if grantee_ids.is_empty() && self.counted_grantees.remove(&grant.grantee) {
+                self.distinct_grantee_count -= 1;
+            }
+        }
+
+        self.grant_ids_by_data_id
+            .get_mut(&grant.data_id)
+            .unwrap_or(&mut vec![])
+            .retain(|id| *id != *grant_id);
+        if self
+            .grant_ids_by_data_id
+            .get(&grant.data_id)
+            .map_or(true, |grant_ids| grant_ids.is_empty())
+        {
+            self._untrack_data_id(&grant.data_id);
+        }
+
+        if let Some(label) = &grant.label {
+            self.grant_ids_by_label
+                .get_mut(label)
+                .unwrap_or(&mut vec![])
+                .retain(|id| *id != *grant_id);
+        }
+    }
+
+    fn _audit(&mut self, action: AuditAction, grant_id: &str) {
+        if self.audit_log_enabled {
+            self.audit_log.push(audit_entry(action, grant_id.into()));
+        }
+    }
+
+    fn _track_data_id(&mut self, data_id: &String) {
+        if !self.data_id_positions.contains_key(data_id) {
+            self.data_id_positions
+                .insert(data_id.clone(), self.data_ids.len());
+            self.data_ids.push(data_id.clone());
+        }
+    }
+
+    // Moves the last data_id into the freed position, so `all_data_ids` order changes as data_ids go away.
+    fn _untrack_data_id(&mut self, data_id: &String) {
+        let Some(position) = self.data_id_positions.remove(data_id) else {
+            return;
+        };
+
+        self.data_ids.swap_remove(position);
+        if let Some(moved) = self.data_ids.get(position) {
+            self.data_id_positions.insert(moved.clone(), position);
+        }
+    }
+
+    // Grants stored before `data_ids` existed aren't in it. After `migrate`, the contract account can pass
+    // their data_ids here (at most `MAX_BATCH_SIZE` per call); any that still have grants get added.
+    pub fn backfill_data_ids(&mut self, data_ids: Vec<String>) -> u64 {
+        require_admin();
+        require_batch_size(data_ids.len());
+
+        let mut added = 0;
+        for data_id in data_ids {
+            let has_grants = self
+                .grant_ids_by_data_id
+                .get(&data_id)
+                .map_or(false, |grant_ids| !grant_ids.is_empty());
+
+            if has_grants && !self.data_id_positions.contains_key(&data_id) {
+                self._track_data_id(&data_id);
+                added += 1;
+            }
+        }
+
+        added
+    }
+
+    // Every data_id that currently has a grant, `limit` (at most `MAX_RESULTS`) at a time. Order is only stable
+    // while no data_id loses its last grant (see `_untrack_data_id`).
+    pub fn all_data_ids(&self, from_index: u64, limit: u64) -> Vec<String> {
+        require_limit(limit);
+
+        self.data_ids
+            .iter()
+            .skip(skip_len(from_index))
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    // Checks both that some active grant (as in `active_grants_for`) exists and that whoever presents it holds
+    // the grantee's key. Anything that doesn't verify (wrong length, non-ed25519 grantee, bad signature) is just
+    // `false`.
+    pub fn verify_grant_with_signature(
+        &self,
+        grantee: PublicKey,
+        data_id: String,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> bool {
+        if grantee.curve_type() != CurveType::ED25519 {
+            return false;
+        }
+
+        let Ok(signature) = <[u8; 64]>::try_from(signature.as_slice()) else {
+            return false;
+        };
+
+        env::ed25519_verify(&signature, &message, public_key_bytes_ref(&grantee))
+            && self
+                ._find_grant_ids(None, Some(grantee), Some(data_id))
+                .iter()
+                .any(|id| self._is_active(id))
+    }
+
+    // Whether `owner` gives `grantee` access to `data_id` right now: like `find_grants` with all three filters,
+    // but only counting active grants (as in `active_grants_for`), and stopping at the first one.
+    pub fn is_granted(&self, owner: PublicKey, grantee: PublicKey, data_id: String) -> bool {
+        let (Some(by_owner), Some(by_grantee), Some(by_data_id)) = (
+            self.grant_ids_by_owner.get(&owner),
+            self.grant_ids_by_grantee.get(&grantee),
+            self.grant_ids_by_data_id.get(&data_id),
+        ) else {
+            return false;
+        };
+
+        by_owner
+            .iter()
+            .any(|id| by_grantee.contains(id) && by_data_id.contains(id) && self._is_active(id))
+    }
+
+    // `is_granted` on any data_id: whether `owner` has a grant to `grantee` at all, active or not.
+    pub fn has_relationship(&self, owner: PublicKey, grantee: PublicKey) -> bool {
+        let (Some(by_owner), Some(by_grantee)) = (
+            self.grant_ids_by_owner.get(&owner),
+            self.grant_ids_by_grantee.get(&grantee),
+        ) else {
+            return false;
+        };
+
+        by_owner.iter().any(|id| by_grantee.contains(id))
+    }
+
+    pub fn owner_has_any_grant(&self, owner: PublicKey) -> bool {
+        self.grant_ids_by_owner
+            .get(&owner)
+            .map_or(false, |grant_ids| !grant_ids.is_empty())
+    }
+
+    // A slice of `limit` (at most `MAX_RESULTS`) of the owner's grant ids in insertion order, without loading
+    // any grant.
+    pub fn grant_ids_for_owner_paged(
+        &self,
+        owner: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<String> {
+        require_limit(limit);
+
+        self.grant_ids_by_owner
+            .get(&owner)
+            .unwrap_or(&vec![])
+            .iter()
+            .skip(skip_len(from_index))
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    // Tail sync: up to `limit` (at most `MAX_RESULTS`) of the owner's grant ids past the first `known_len`, for
+    // mirrors that already have those. Deleting a grant shifts every later id down one position, so this is
+    // only sound for a mirror that applies deletions (e.g. from `grant_deleted` events) and shrinks
+    // `known_len` to match.
+    pub fn owner_grant_ids_after(
+        &self,
+        owner: PublicKey,
+        known_len: u64,
+        limit: u64,
+    ) -> Vec<String> {
+        self.grant_ids_for_owner_paged(owner, known_len, limit)
+    }
+
+    // The owner's last `n` (at most `MAX_RESULTS`) grants, newest first. That's the end of their index, which
+    // only ever appends, so a re-inserted grant counts as new.
+    pub fn recent_grants_for_owner(&self, owner: PublicKey, n: u64) -> Vec<Grant> {
+        require!(n <= MAX_RESULTS as u64, ERR_N_TOO_LARGE);
+
+        self.grant_ids_by_owner
+            .get(&owner)
+            .unwrap_or(&vec![])
+            .iter()
+            .rev()
+            .take(n as usize)
+            .filter_map(|id| self.grants_by_id.get(id).cloned())
+            .collect()
+    }
+
+    // Grants whose timelock has already passed, i.e. that the owner could delete right now.
+    // Walks the owner's grants in insertion order and stops after `limit` matches.
+    pub fn find_stale_grants(&self, owner: PublicKey, limit: u64) -> Vec<(String, Grant)> {
+        self.grant_ids_by_owner
+            .get(&owner)
+            .unwrap_or(&vec![])
+            .iter()
+            .filter_map(|id| Some((id.clone(), self.grants_by_id.get(id)?.clone())))
+            .filter(|(_, grant)| grant.locked_until != 0 && !grant.is_timelocked())
+            .take(limit as usize)
+            .collect()
+    }
+
+    // The owner's grants with a created_at in [from, to], in insertion order. Grants from before created_at
+    // was recorded never match.
+    pub fn find_grants_created_between(
+        &self,
+        owner: PublicKey,
+        from: EpochHeight,
+        to: EpochHeight,
+    ) -> Vec<Grant> {
+        let grants: Vec<Grant> = self
+            ._find_grants(Some(owner), None, None)
+            .into_iter()
+            .filter(|grant| grant.created_at.map_or(false, |at| from <= at && at <= to))
+            .collect();
+        require_paginated_past_max_results(grants.len());
+
+        grants
+    }
+
+    // How many grantees hold at least one grant. On contracts upgraded with `migrate` this only counts
+    // forward from the upgrade: it starts at 0, so grantees whose grants all predate it aren't counted
+    // until they get a new one. Deleting the grants of a grantee that isn't counted leaves the count alone.
+    pub fn distinct_grantees(&self) -> u64 {
+        self.distinct_grantee_count
+    }
+
+    // (grantee, data_id, expires_at) for the owner's grants that haven't expired yet but will within
+    // `within_nanos`, in insertion order. Only looks at `limit` (at most `MAX_RESULTS`) of the owner's grants,
+    // from `from_index` in their index, so owners with more go through them a page at a time.
+    pub fn grantees_expiring_within(
+        &self,
+        owner: PublicKey,
+        within_nanos: EpochHeight,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(PublicKey, String, EpochHeight)> {
+        let now = env::block_timestamp();
+        let until = now.saturating_add(within_nanos);
+
+        self._grants_page(self.grant_ids_by_owner.get(&owner), from_index, limit)
+            .into_iter()
+            .filter_map(|grant| {
+                let expires_at = grant.expires_at?;
+                (now < expires_at && expires_at <= until).then_some((
+                    grant.grantee,
+                    grant.data_id,
+                    expires_at,
+                ))
+            })
+            .collect()
+    }
+
+    // (oldest, newest) created_at across `limit` (at most `MAX_RESULTS`) of the owner's grants, from
+    // `from_index` in their index, or None if none of them has one. Owners with more combine the pages.
+    pub fn grant_time_bounds(
+        &self,
+        owner: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> Option<(EpochHeight, EpochHeight)> {
+        self._grants_page(self.grant_ids_by_owner.get(&owner), from_index, limit)
+            .iter()
+            .filter_map(|grant| grant.created_at)
+            .fold(None, |bounds, at| match bounds {
+                None => Some((at, at)),
+                Some((oldest, newest)) => Some((oldest.min(at), newest.max(at))),
+            })
+    }
+
+    // The owner's data_ids granted to more than `threshold` distinct grantees, with that count, in the order
+    // each data_id was first granted. The data_ids come from `limit` (at most `MAX_RESULTS`) of the owner's
+    // grants, from `from_index` in their index, and each one's count from all of the owner's grants on it; a
+    // data_id granted more than once can show up on more than one page.
+    pub fn over_shared_data_ids(
+        &self,
+        owner: PublicKey,
+        threshold: u64,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(String, u64)> {
+        let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+
+        self._grants_page(self.grant_ids_by_owner.get(&owner), from_index, limit)
+            .into_iter()
+            .map(|grant| grant.data_id)
+            .filter(|data_id| seen.insert(data_id.clone()))
+            .filter_map(|data_id| {
+                let grantees: std::collections::HashSet<PublicKey> = self
+                    ._find_grants(Some(owner.clone()), None, Some(data_id.clone()))
+                    .into_iter()
+                    .map(|grant| grant.grantee)
+                    .collect();
+                let count = grantees.len() as u64;
+
+                (count > threshold).then_some((data_id, count))
+            })
+            .collect()
+    }
+
+    // The owner's data_ids in the order they were first granted, `data_ids_limit` (at most `MAX_BATCH_SIZE`)
+    // at a time, each with its number of grants and up to `DASHBOARD_SAMPLE_SIZE` distinct grantees.
+    pub fn owner_dashboard(
+        &self,
+        owner: PublicKey,
+        data_ids_from: u64,
+        data_ids_limit: u64,
+    ) -> Vec<DataIdSummary> {
+        require!(data_ids_limit <= MAX_BATCH_SIZE as u64, ERR_BATCH_TOO_LARGE);
+
+        // Only the owner's grants up to the last data_id on the page are loaded to find the page's data_ids,
+        // then only the grants on those data_ids to summarize them.
+        let wanted = skip_len(data_ids_from).saturating_add(data_ids_limit as usize);
+        let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+        let mut data_ids: Vec<String> = vec![];
+        for id in self.grant_ids_by_owner.get(&owner).unwrap_or(&vec![]) {
+            if data_ids.len() >= wanted {
+                break;
+            }
+            if let Some(grant) = self.grants_by_id.get(id) {
+                if seen.insert(grant.data_id.clone()) {
+                    data_ids.push(grant.data_id.clone());
+                }
+            }
+        }
+
+        data_ids
+            .into_iter()
+            .skip(skip_len(data_ids_from))
+            .map(|data_id| {
+                let grants = self._find_grants(Some(owner.clone()), None, Some(data_id.clone()));
+                let mut sample_grantees: Vec<PublicKey> = vec![];
+                for grant in &grants {
+                    if sample_grantees.len() < DASHBOARD_SAMPLE_SIZE
+                        && !sample_grantees.contains(&grant.grantee)
+                    {
+                        sample_grantees.push(grant.grantee.clone());
+                    }
+                }
+
+                DataIdSummary {
+                    data_id,
+                    grant_count: grants.len() as u64,
+                    sample_grantees,
+                }
+            })
+            .collect()
+    }
+
+    // Distinct data_ids `source_owner` has granted and `target_owner` hasn't (to anyone), in the order
+    // `source_owner` first granted them. Only looks at `limit` (at most `MAX_RESULTS`) of `source_owner`'s
+    // grants, from `from_index` in their index; a data_id granted more than once can show up on more than
+    // one page.
+    pub fn data_ids_missing_on(
+        &self,
+        source_owner: PublicKey,
+        target_owner: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<String> {
+        let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+
+        self._grants_page(
+            self.grant_ids_by_owner.get(&source_owner),
+            from_index,
+            limit,
+        )
+        .into_iter()
+        .map(|grant| grant.data_id)
+        .filter(|data_id| {
+            seen.insert(data_id.clone())
+                && self
+                    ._find_grant_ids(Some(target_owner.clone()), None, Some(data_id.clone()))
+                    .is_empty()
+        })
+        .collect()
+    }
+
+    // Distinct data_ids both grantees have a grant on (from any owners, not necessarily the same one), in the
+    // order `grantee_a` was first granted them. Only looks at `limit` (at most `MAX_RESULTS`) of `grantee_a`'s
+    // grants, from `from_index` in their index; a data_id they hold several grants on can show up on more
+    // than one page.
+    pub fn common_data_ids(
+        &self,
+        grantee_a: PublicKey,
+        grantee_b: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<String> {
+        let mut common: Vec<String> = vec![];
+
+        for grant in self._grants_page(self.grant_ids_by_grantee.get(&grantee_a), from_index, limit)
+        {
+            if !common.contains(&grant.data_id)
+                && !self
+                    ._find_grant_ids(None, Some(grantee_b.clone()), Some(grant.data_id.clone()))
+                    .is_empty()
+            {
+                common.push(grant.data_id);
+            }
+        }
+
+        common
+    }
+
+    // Distinct data_ids across all owners, in the order they were first granted. Only looks at `limit` (at most
+    // `MAX_RESULTS`) of the grantee's grants, from `from_index` in their index; a data_id they hold several
+    // grants on can show up on more than one page.
+    pub fn data_ids_for_grantee(
+        &self,
+        grantee: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<String> {
+        let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+
+        self._grants_page(self.grant_ids_by_grantee.get(&grantee), from_index, limit)
+            .into_iter()
+            .map(|grant| grant.data_id)
+            .filter(|data_id| seen.insert(data_id.clone()))
+            .collect()
+    }
+
+    // Distinct owners that granted `grantee` anything, in the order of their first grant. Pages through the
+    // grantee's grants like `data_ids_for_grantee`, so an owner can show up on more than one page.
+    pub fn owners_for_grantee(
+        &self,
+        grantee: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<PublicKey> {
+        let mut seen: std::collections::HashSet<PublicKey> = std::collections::HashSet::new();
+
+        self._grants_page(self.grant_ids_by_grantee.get(&grantee), from_index, limit)
+            .into_iter()
+            .map(|grant| grant.owner)
+            .filter(|owner| seen.insert(owner.clone()))
+            .collect()
+    }
+
+    // Compound consent: true only if the grantee holds an active grant (as in `active_grants_for`, from any
+    // owner) on every one of `data_ids`.
+    pub fn has_grants_on_all(&self, grantee: PublicKey, data_ids: Vec<String>) -> bool {
+        require!(!data_ids.is_empty(), ERR_EMPTY_DATA_IDS);
+        require_batch_size(data_ids.len());
+
+        let empty = vec![];
+        let by_grantee = self.grant_ids_by_grantee.get(&grantee).unwrap_or(&empty);
+
+        data_ids.iter().all(|data_id| {
+            let by_data_id = self.grant_ids_by_data_id.get(data_id).unwrap_or(&empty);
+
+            by_grantee
+                .iter()
+                .any(|id| by_data_id.contains(id) && self._is_active(id))
+        })
+    }
+
+    // Whether each (grantee, data_id) has an active grant from any owner, aligned with `queries`, like calling
+    // `active_grants_for` once per pair but stopping at each pair's first match.
+    pub fn has_grants_batch(&self, queries: Vec<(PublicKey, String)>) -> Vec<bool> {
+        require_batch_size(queries.len());
+
+        let empty = vec![];
+
+        queries
+            .iter()
+            .map(|(grantee, data_id)| {
+                let by_grantee = self.grant_ids_by_grantee.get(grantee).unwrap_or(&empty);
+                let by_data_id = self.grant_ids_by_data_id.get(data_id).unwrap_or(&empty);
+
+                by_grantee
+                    .iter()
+                    .any(|id| by_data_id.contains(id) && self._is_active(id))
+            })
+            .collect()
+    }
+
+    // The grantee's active grants on `data_ids`, but only if `has_grants_on_all` holds: a grantee missing any
+    // one of the data_ids gets nothing back, where a union of per-data_id searches would return the rest.
+    pub fn find_grants_all_data_ids(
+        &self,
+        grantee: PublicKey,
+        data_ids: Vec<String>,
+    ) -> Vec<Grant> {
+        if !self.has_grants_on_all(grantee.clone(), data_ids.clone()) {
+            return vec![];
+        }
+
+        self._find_grant_ids(None, Some(grantee), None)
+            .iter()
+            .filter(|id| self._is_active(id))
+            .filter_map(|id| self.grants_by_id.get(id).cloned())
+            .filter(|grant| data_ids.contains(&grant.data_id))
+            .collect()
+    }
+
+    // Every owner's grants with `label` share one bucket, and `owner` only filters it, so like `find_grants`
+    // this fails once more than `MAX_RESULTS` grants match.
+    pub fn find_grants_by_label(&self, owner: Option<PublicKey>, label: String) -> Vec<Grant> {
+        let grants: Vec<Grant> = self
+            .grant_ids_by_label
+            .get(&label)
+            .unwrap_or(&vec![])
+            .iter()
+            .filter_map(|id| self.grants_by_id.get(id))
+            .filter(|grant| owner.as_ref().map_or(true, |owner| grant.owner == *owner))
+            .cloned()
+            .collect();
+        require_paginated_past_max_results(grants.len());
+
+        grants
+    }
+
+    pub fn get_grant(&self, grant_id: String) -> Option<Grant> {
+        self.grants_by_id.get(&grant_id).cloned()
+    }
+
+    // Nanoseconds until the grant's timelock passes, i.e. until its owner can delete it: positive while it's
+    // timelocked, 0 or less once it isn't. Only the timelock counts, not the override or `immutable`.
+    pub fn time_until_revocable(&self, grant_id: String) -> Option<i128> {
+        self.grants_by_id.get(&grant_id).map(|grant| {
+            // Timelocked up to and including locked_until, so revocable from the nanosecond after.
+            grant.locked_until as i128 + 1 - env::block_timestamp() as i128
+        })
+    }
+
+    // Results line up with `grant_ids`, with None for ids that don't (or no longer) exist.
+    pub fn get_grants(&self, grant_ids: Vec<String>) -> Vec<Option<Grant>> {
+        require_batch_size(grant_ids.len());
+
+        grant_ids
+            .iter()
+            .map(|id| self.grants_by_id.get(id).cloned())
+            .collect()
+    }
+
+    // The ids `owner`'s grants with these coordinates have (or would have), in input order, derived exactly as
+    // inserting them would. Only grantee, data_id and locked_until are read from each of the (at most
+    // `MAX_BATCH_SIZE`) `grants`; nothing is looked up, so the grants needn't exist.
+    pub fn compute_grant_ids(&self, grants: Vec<GrantInput>, owner: PublicKey) -> Vec<String> {
+        require_batch_size(grants.len());
+
+        grants
+            .into_iter()
+            .map(|input| {
+                derive_grant_id(&Grant {
+                    owner: owner.clone(),
+                    grantee: input.grantee,
+                    data_id: input.data_id,
+                    locked_until: input.locked_until,
+                    expires_at: None,
+                    expired: false,
+                    label: None,
+                    required_attestor: None,
+                    created_at: None,
+                    valid_from: None,
+                    immutable: false,
+                    co_owners: vec![],
+                    sponsor: None,
+                })
+            })
+            .collect()
+    }
+
+    // Existence only, aligned with `grant_ids`, for reconciling an off-chain mirror without loading grants.
+    pub fn which_exist(&self, grant_ids: Vec<String>) -> Vec<bool> {
+        require_batch_size(grant_ids.len());
+
+        grant_ids
+            .iter()
+            .map(|id| self.grants_by_id.contains_key(id))
+            .collect()
+    }
+
+    pub fn grants_for(&self, grantee: PublicKey, data_id: String) -> Vec<Grant> {
+        self._find_grants(None, Some(grantee), Some(data_id))
+    }
+
+    // `grants_for` restricted to one owner's grants. Same as `find_grants` with all three filters, named for
+    // the access-check hot path; it returns early if any of the three indexes is empty.
+    pub fn grants_for_from_owner(
+        &self,
+        owner: PublicKey,
+        grantee: PublicKey,
+        data_id: String,
+    ) -> Vec<Grant> {
+        let (Some(by_owner), Some(by_grantee), Some(by_data_id)) = (
+            self.grant_ids_by_owner.get(&owner),
+            self.grant_ids_by_grantee.get(&grantee),
+            self.grant_ids_by_data_id.get(&data_id),
+        ) else {
+            return vec![];
+        };
+
+        by_owner
+            .iter()
+            .filter(|id| by_grantee.contains(id) && by_data_id.contains(id))
+            .filter_map(|id| self.grants_by_id.get(id).cloned())
+            .collect()
+    }
+
+    // The grants that currently give `grantee` access to `data_id`: like `grants_for`, minus expired grants,
+    // those whose valid_from hasn't come yet, and those still waiting on their required attestor.
+    pub fn active_grants_for(&self, grantee: PublicKey, data_id: String) -> Vec<Grant> {
+        self._find_grant_ids(None, Some(grantee), Some(data_id))
+            .iter()
+            .filter(|id| self._is_active(id))
+            .filter_map(|id| self.grants_by_id.get(id).cloned())
+            .collect()
+    }
+
+    // The `data_ids` (at most `MAX_BATCH_SIZE`) that `grantee` has an active grant on, in input order, as in
+    // `active_grants_for`. Each data_id stops at its first active grant.
+    pub fn active_data_ids_for_grantee(
+        &self,
+        grantee: PublicKey,
+        data_ids: Vec<String>,
+    ) -> Vec<String> {
+        require_batch_size(data_ids.len());
+
+        let empty = vec![];
+        let by_grantee = self.grant_ids_by_grantee.get(&grantee).unwrap_or(&empty);
+
+        data_ids
+            .into_iter()
+            .filter(|data_id| {
+                let by_data_id = self.grant_ids_by_data_id.get(data_id).unwrap_or(&empty);
+
+                by_grantee
+                    .iter()
+                    .any(|id| by_data_id.contains(id) && self._is_active(id))
+            })
+            .collect()
+    }
+
+    fn _is_active(&self, grant_id: &String) -> bool {
+        let Some(grant) = self.grants_by_id.get(grant_id) else {
+            return false;
+        };
+
+        !grant.is_expired()
+            && !grant.is_pending()
+            && (grant.required_attestor.is_none() || self.attested_grant_ids.contains(grant_id))
+    }
+
+    pub fn attest(&mut self, grant_id: String) {
+        self.require_writable();
+
+        let grant = self
+            .grants_by_id
+            .get(&grant_id)
+            .unwrap_or_else(|| env::panic_str(ERR_GRANT_DOES_NOT_EXIST))
+            .clone();
+
+        require!(
+            grant.required_attestor.as_ref() == Some(&env::predecessor_account_id()),
+            ERR_NOT_REQUIRED_ATTESTOR
+        );
+
+        self.attested_grant_ids.insert(grant_id);
+
+        FractalRegistryEvents::GrantAttested {
+            owner: grant.owner,
+            grantee: grant.grantee,
+            data_id: grant.data_id,
+            locked_until: grant.locked_until,
+            attestor: env::predecessor_account_id(),
+            trace: EventTrace::now(),
+        }
+        .emit();
+    }
+
+    // Results come back in the insertion order of the first index searched (owner's if given, grantee's otherwise).
+    // Every index only ever appends, so that's also the order the grants were stored in, and any combination
+    // of filters lists the grants it shares with another in the same relative order.
+    // Deleting keeps the relative order of what's left, and re-inserting puts the grant at the end.
+    // Clients rely on this, so `find_grants_keeps_head_index_insertion_order` guards it.
+    // `exclude_grantee` drops that grantee's grants from the results, e.g. to see who else has access.
+    // `locked_until` keeps only grants with exactly that lock. Unlike `delete_grant`'s, Some(0) isn't a
+    // wildcard here: it means grants with no lock. `MAX_RESULTS` caps what's left after both filters.
+    pub fn find_grants(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+        exclude_grantee: Option<PublicKey>,
+        locked_until: Option<EpochHeight>,
+    ) -> Vec<Grant> {
+        let grants =
+            self._find_filtered_grants(owner, grantee, data_id, exclude_grantee, locked_until);
+        require_paginated_past_max_results(grants.len());
+
+        grants
+    }
+
+    fn _find_filtered_grants(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+        exclude_grantee: Option<PublicKey>,
+        locked_until: Option<EpochHeight>,
+    ) -> Vec<Grant> {
+        let mut grants = self._find_grants(owner, grantee, data_id);
+        if let Some(excluded) = exclude_grantee {
+            grants.retain(|grant| grant.grantee != excluded);
+        }
+        if let Some(locked_until) = locked_until {
+            grants.retain(|grant| grant.locked_until == locked_until);
+        }
+
+        grants
+    }
+
+    // `find_grants` for untrusted front-ends: every query it would panic on comes back as a FindResult
+    // variant instead, e.g. `"missing_filter"`, while matches come back as `{"ok": [...]}`.
+    pub fn try_find_grants(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+        exclude_grantee: Option<PublicKey>,
+        locked_until: Option<EpochHeight>,
+    ) -> FindResult {
+        if owner.is_none() && grantee.is_none() {
+            return FindResult::MissingFilter;
+        }
+
+        let grants =
+            self._find_filtered_grants(owner, grantee, data_id, exclude_grantee, locked_until);
+        if grants.len() > MAX_RESULTS {
+            return FindResult::TooManyResults;
+        }
+
+        FindResult::Ok(grants)
+    }
+
+    // Same search as `find_grants`, split into the grants that are active (as in `active_grants_for`) and
+    // those that aren't: expired, not valid yet, or waiting on their required attestor.
+    pub fn find_grants_partitioned(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+    ) -> PartitionedGrants {
+        let grant_ids = self._find_grant_ids(owner, grantee, data_id);
+        require_paginated_past_max_results(grant_ids.len());
+
+        let (active, inactive): (Vec<String>, Vec<String>) =
+            grant_ids.into_iter().partition(|id| self._is_active(id));
+        let load = |ids: Vec<String>| -> Vec<Grant> {
+            ids.iter()
+                .filter_map(|id| self.grants_by_id.get(id).cloned())
+                .collect()
+        };
+
+        PartitionedGrants {
+            active: load(active),
+            inactive: load(inactive),
+        }
+    }
+
+    // Like `find_grants`, bucketed by data_id. Buckets follow the order each data_id first shows up in there.
+    pub fn find_grants_grouped(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+    ) -> Vec<(String, Vec<Grant>)> {
+        let grants = self._find_grants(owner, grantee, None);
+        require_paginated_past_max_results(grants.len());
+
+        let mut groups: Vec<(String, Vec<Grant>)> = vec![];
+
+        for grant in grants {
+            match groups
+                .iter_mut()
+                .find(|(data_id, _)| *data_id == grant.data_id)
+            {
+                Some((_, grants)) => grants.push(grant),
+                None => groups.push((grant.data_id.clone(), vec![grant])),
+            }
+        }
+
+        groups
+    }
+
+    fn _find_grants(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+    ) -> Vec<Grant> {
+        self._find_grant_ids(owner, grantee, data_id)
+            .iter()
+            .filter_map(|id| self.grants_by_id.get(id).cloned())
+            .collect()
+    }
+
+    // Same search as `find_grants`, keeping only `fields` of each grant (all of them when omitted).
+    pub fn find_grants_projected(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+        fields: Option<Vec<GrantField>>,
+    ) -> Vec<GrantProjection> {
+        let fields = fields.unwrap_or_else(|| ALL_GRANT_FIELDS.to_vec());
+
+        let grants = self._find_grants(owner, grantee, data_id);
+        require_paginated_past_max_results(grants.len());
+
+        grants
+            .into_iter()
+            .map(|grant| grant.project(&fields))
+            .collect()
+    }
+
+    // Same search as `find_grants`, paginated, with each grant's id and whether it's still timelocked.
+    pub fn find_grants_full(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<GrantEntry> {
+        let grant_ids = self._find_grant_ids(owner, grantee, data_id);
+        if limit.is_none() {
+            require_paginated_past_max_results(
+                grant_ids
+                    .len()
+                    .saturating_sub(skip_len(from_index.unwrap_or(0))),
+            );
+        }
+
+        grant_ids
+            .into_iter()
+            .skip(skip_len(from_index.unwrap_or(0)))
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .filter_map(|grant_id| {
+                let grant = self.grants_by_id.get(&grant_id)?.clone();
+
+                Some(GrantEntry {
+                    grant_id,
+                    is_timelocked: grant.is_timelocked(),
+                    grant,
+                })
+            })
+            .collect()
+    }
+
+    // The ids `find_grants` would return, uncapped. Large result sets can be fetched as ids once and then
+    // hydrated `MAX_BATCH_SIZE` at a time with `get_grants`, instead of redoing the search for every page.
+    pub fn find_grant_ids(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+    ) -> Vec<String> {
+        self._find_grant_ids(owner, grantee, data_id)
+    }
+
+    // Same search as `find_grants`, with each grant's id embedded in its JSON.
+    pub fn find_grants_with_embedded_id(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+    ) -> Vec<GrantWithId> {
+        let grant_ids = self._find_grant_ids(owner, grantee, data_id);
+        require_paginated_past_max_results(grant_ids.len());
+
+        grant_ids
+            .into_iter()
+            .filter_map(|grant_id| {
+                Some(GrantWithId {
+                    grant: self.grants_by_id.get(&grant_id)?.clone(),
+                    grant_id,
+                })
+            })
+            .collect()
+    }
+
+    // One search combining every filter in `query`, paginated like `find_grants_full`. Candidates come from
+    // the owner/grantee/data_id indexes (at least one of them is required), and the rest of `query` is then
+    // checked against each candidate, so a narrow index filter keeps this cheap.
+    pub fn find_grants_advanced(
+        &self,
+        query: GrantQuery,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<Grant> {
+        let candidates = match (&query.owner, &query.grantee, &query.data_id) {
+            (None, None, Some(data_id)) => self
+                .grant_ids_by_data_id
+                .get(data_id)
+                .cloned()
+                .unwrap_or_default(),
+            (None, None, None) => env::panic_str(ERR_FILTER_REQUIRED),
+            _ => self._find_grant_ids(
+                query.owner.clone(),
+                query.grantee.clone(),
+                query.data_id.clone(),
+            ),
+        };
+
+        let grants: Vec<Grant> = candidates
+            .iter()
+            .filter_map(|id| self.grants_by_id.get(id))
+            .filter(|grant| query.matches(grant))
+            .skip(skip_len(from_index.unwrap_or(0)))
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .cloned()
+            .collect();
+        if limit.is_none() {
+            require_paginated_past_max_results(grants.len());
+        }
+
+        grants
+    }
+
+    // Cursor pagination over `find_grants`: pass the previous page's `next_cursor` as `after` to resume.
+    // The cursor is the id of the page's last grant, so grants inserted or deleted between pages can't make
+    // the next page skip anything (new grants show up at the end). The one exception is deleting the cursor's
+    // own grant, which makes the cursor unusable, and the call fails instead of silently skipping.
+    pub fn find_grants_page(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+        after: Option<String>,
+        limit: u64,
+    ) -> GrantsPage {
+        let grant_ids = self._find_grant_ids(owner, grantee, data_id);
+
+        let start = match after {
+            None => 0,
+            Some(cursor) => {
+                grant_ids
+                    .iter()
+                    .position(|id| *id == cursor)
+                    .unwrap_or_else(|| env::panic_str(ERR_CURSOR_INVALID))
+                    + 1
+            }
+        };
+
+        let page: Vec<String> = grant_ids
+            .iter()
+            .skip(start)
+            .take(limit as usize)
+            .cloned()
+            .collect();
+
+        let next_cursor = page
+            .last()
+            .filter(|_| start + page.len() < grant_ids.len())
+            .cloned();
+
+        GrantsPage {
+            grants: page
+                .iter()
+                .filter_map(|id| self.grants_by_id.get(id).cloned())
+                .collect(),
+            next_cursor,
+        }
+    }
+
+    // The grants of `limit` (at most `MAX_RESULTS`) of an index's ids, from `from_index`.
+    fn _grants_page(
+        &self,
+        grant_ids: Option<&Vec<String>>,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<Grant> {
+        require_limit(limit);
+
+        grant_ids
+            .into_iter()
+            .flatten()
+            .skip(skip_len(from_index))
+            .take(limit as usize)
+            .filter_map(|id| self.grants_by_id.get(id).cloned())
+            .collect()
+    }
+
+    fn _find_grant_ids(
+        &self,
+        owner: Option<PublicKey>,
+        grantee: Option<PublicKey>,
+        data_id: Option<String>,
+    ) -> Vec<String> {
+        let mut grant_id_searches = Vec::new();
+
+        require!(
+            owner.is_some() || grantee.is_some(),
+            ERR_OWNER_OR_GRANTEE_REQUIRED,
+        );
+
+        let empty = vec![];
+        if let Some(owner) = owner {
+            grant_id_searches.push(self.grant_ids_by_owner.get(&owner).unwrap_or(&empty));
+        }
+
+        if let Some(grantee) = grantee {
+            grant_id_searches.push(self.grant_ids_by_grantee.get(&grantee).unwrap_or(&empty));
+        }
+
+        if let Some(data_id) = data_id {
+            grant_id_searches.push(self.grant_ids_by_data_id.get(&data_id).unwrap_or(&empty));
+        }
+
+        let Some((head, tail)) = grant_id_searches.split_first() else {
+            return vec![];
+        };
+
+        head.iter()
+            .filter(|id| tail.iter().all(|s| s.contains(id)))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use near_sdk::serde_json::{self, Value};
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn public_key(seed: u8) -> PublicKey {
+        PublicKey::from_parts(CurveType::ED25519, vec![seed; 32]).unwrap()
+    }
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+
+    fn signed_by(signer: &PublicKey) {
+        signed_by_at(signer, NOW);
+    }
+
+    fn signed_by_at(signer: &PublicKey, block_timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .signer_account_pk(signer.clone())
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    #[test]
+    fn insert_grant_multi_inserts_one_grant_per_data_id() {
+        let owner = public_key(1);
+        let grantee = public_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant_multi(
+            grantee.clone(),
+            vec!["A1".into(), "A2".into(), "A3".into()],
+            None,
+            None,
+        );
+
+        let data_ids: Vec<String> = contract
+            .find_grants(None, Some(grantee), None, None, None)
+            .into_iter()
+            .map(|grant| grant.data_id)
+            .collect();
+        assert_eq!(data_ids, vec!["A1", "A2", "A3"]);
+        assert_eq!(logged_events().len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant already exists")]
+    fn insert_grant_multi_rejects_repeated_data_ids() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant_multi(public_key(2), vec!["A1".into(), "A1".into()], None, None);
+    }
+
+    #[test]
+    fn find_grants_keeps_head_index_insertion_order() {
+        let owner = public_key(1);
+        let (bob, charlie) = (public_key(2), public_key(3));
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(charlie.clone(), "A2".into(), None);
+        contract.insert_grant(bob.clone(), "A1".into(), None);
+        contract.insert_grant(bob.clone(), "A2".into(), None);
+        contract.insert_grant(charlie.clone(), "A1".into(), None);
+
+        let coordinates = |grants: Vec<Grant>| -> Vec<(PublicKey, String)> {
+            grants
+                .into_iter()
+                .map(|grant| (grant.grantee, grant.data_id))
+                .collect()
+        };
+
+        assert_eq!(
+            coordinates(contract.find_grants(Some(owner.clone()), None, None, None, None)),
+            vec![
+                (charlie.clone(), "A2".into()),
+                (bob.clone(), "A1".into()),
+                (bob.clone(), "A2".into()),
+                (charlie.clone(), "A1".into()),
+            ]
+        );
+        assert_eq!(
+            coordinates(contract.find_grants(None, Some(charlie.clone()), None, None, None)),
+            vec![
+                (charlie.clone(), "A2".into()),
+                (charlie.clone(), "A1".into())
+            ]
+        );
+        assert_eq!(
+            coordinates(contract.find_grants(
+                Some(owner.clone()),
+                None,
+                Some("A1".into()),
+                None,
+                None
+            )),
+            vec![(bob.clone(), "A1".into()), (charlie.clone(), "A1".into())]
+        );
+
+        contract.delete_grant(bob.clone(), "A1".into(), None);
+        contract.insert_grant(bob.clone(), "A1".into(), None);
+
+        assert_eq!(
+            coordinates(contract.find_grants(Some(owner), None, None, None, None)),
+            vec![
+                (charlie.clone(), "A2".into()),
+                (bob.clone(), "A2".into()),
+                (charlie, "A1".into()),
+                (bob, "A1".into()),
+            ]
+        );
+    }
+
+    fn called_by_contract() {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(env::current_account_id())
+            .block_timestamp(NOW)
+            .build());
+    }
+
+    // Skips plain logs, like the `gas-profiling` feature's.
+    fn logged_events() -> Vec<Value> {
+        get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|event| serde_json::from_str(event).unwrap())
+            .collect()
+    }
+
+    fn signing_key(seed: u8) -> (SigningKey, PublicKey) {
+        let signing_key = SigningKey::from_bytes(&[seed; 32]);
+        let public_key = PublicKey::from_parts(
+            CurveType::ED25519,
+            signing_key.verifying_key().to_bytes().to_vec(),
+        )
+        .unwrap();
+
+        (signing_key, public_key)
+    }
+
+    #[test]
+    fn verify_grant_with_signature_checks_grant_and_signature() {
+        let owner = public_key(1);
+        let (grantee_sk, grantee) = signing_key(2);
+        let message = b"I am the grantee".to_vec();
+        let signature = grantee_sk.sign(&message).to_bytes().to_vec();
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        assert!(!contract.verify_grant_with_signature(
+            grantee.clone(),
+            "A1".into(),
+            message.clone(),
+            signature.clone(),
+        ));
+
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+
+        assert!(contract.verify_grant_with_signature(
+            grantee.clone(),
+            "A1".into(),
+            message.clone(),
+            signature.clone(),
+        ));
+        assert!(!contract.verify_grant_with_signature(
+            grantee.clone(),
+            "A1".into(),
+            b"something else".to_vec(),
+            signature,
+        ));
+        assert!(!contract.verify_grant_with_signature(grantee, "A1".into(), message, vec![0; 64],));
+    }
+
+    #[test]
+    fn delete_grants_for_data_id_skips_timelocked_grants() {
+        let owner = public_key(1);
+        let (bob, charlie, dave, eve) =
+            (public_key(2), public_key(3), public_key(4), public_key(5));
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(bob.clone(), "A1".into(), None);
+        contract.insert_grant(charlie.clone(), "A1".into(), None);
+        contract.insert_grant(dave.clone(), "A1".into(), None);
+        contract.insert_grant(eve.clone(), "A1".into(), Some(NOW + 1));
+        contract.insert_grant(bob.clone(), "A2".into(), None);
+
+        assert_eq!(contract.delete_grants_for_data_id("A1".into()), 3);
+        assert_eq!(logged_events().len(), 5 + 3);
+
+        let remaining: Vec<(PublicKey, String)> = contract
+            .find_grants(Some(owner), None, None, None, None)
+            .into_iter()
+            .map(|grant| (grant.grantee, grant.data_id))
+            .collect();
+        assert_eq!(remaining, vec![(eve, "A1".into()), (bob, "A2".into())]);
+    }
+
+    #[test]
+    fn is_granted_matches_any_locked_until() {
+        let owner = public_key(1);
+        let (bob, charlie) = (public_key(2), public_key(3));
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        assert!(!contract.is_granted(owner.clone(), bob.clone(), "A1".into()));
+
+        contract.insert_grant(bob.clone(), "A1".into(), Some(NOW + 1));
+        contract.insert_grant(charlie.clone(), "A2".into(), None);
+
+        assert!(contract.is_granted(owner.clone(), bob.clone(), "A1".into()));
+        assert!(!contract.is_granted(owner.clone(), bob.clone(), "A2".into()));
+        assert!(!contract.is_granted(owner.clone(), charlie, "A1".into()));
+        assert!(!contract.is_granted(public_key(9), bob, "A1".into()));
+    }
+
+    #[test]
+    fn events_share_the_schema_version() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.delete_grant(public_key(2), "A1".into(), None);
+
+        let events = logged_events();
+        assert_eq!(events.len(), 2);
+        for (event, name) in events.iter().zip(["grant_inserted", "grant_deleted"]) {
+            assert_eq!(event["standard"], "FractalRegistry");
+            assert_eq!(event["version"], "2.7.0");
+            assert_eq!(event["event"], name);
+        }
+    }
+
+    #[test]
+    fn find_stale_grants_only_returns_passed_timelocks() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), Some(NOW - 2));
+        contract.insert_grant(public_key(2), "A3".into(), Some(NOW + 1));
+        contract.insert_grant(public_key(3), "A1".into(), Some(NOW - 1));
+
+        let stale = contract.find_stale_grants(owner.clone(), 10);
+        let stale_ids: Vec<String> = stale.iter().map(|(id, _)| id.clone()).collect();
+        let stale_ids_from_grants: Vec<String> = stale
+            .iter()
+            .map(|(_, grant)| derive_grant_id(grant))
+            .collect();
+        let locks: Vec<EpochHeight> = stale.iter().map(|(_, grant)| grant.locked_until).collect();
+
+        assert_eq!(locks, vec![NOW - 2, NOW - 1]);
+        assert_eq!(stale_ids, stale_ids_from_grants);
+        assert_eq!(contract.find_stale_grants(owner, 1).len(), 1);
+    }
+
+    #[test]
+    fn storage_keys_keep_their_deployed_prefixes() {
+        use near_sdk::IntoStorageKey;
+
+        let prefixes: Vec<Vec<u8>> = [
+            StorageKey::GrantsById,
+            StorageKey::GrantIdsByOwner,
+            StorageKey::GrantIdsByGrantee,
+            StorageKey::GrantIdsByDataId,
+        ]
+        .iter()
+        .map(|key| key.into_storage_key())
+        .collect();
+
+        assert_eq!(
+            prefixes,
+            vec![b"g".to_vec(), b"h".to_vec(), b"i".to_vec(), b"j".to_vec()]
+        );
+    }
+
+    fn flush(contract: &mut FractalRegistry) {
+        contract.grants_by_id.flush();
+        contract.grant_ids_by_owner.flush();
+        contract.grant_ids_by_grantee.flush();
+        contract.grant_ids_by_data_id.flush();
+        contract.grant_ids_by_label.flush();
+        contract.default_timelock_nanos_by_owner.flush();
+        contract.idempotency_keys.flush();
+        contract.idempotency_queue.flush();
+        contract.data_ids.flush();
+        contract.data_id_positions.flush();
+        contract.data_id_claims.flush();
+        contract.audit_log.flush();
+    }
+
+    #[test]
+    fn collections_dont_see_each_others_entries() {
+        let owner = public_key(1);
+        let grantee = public_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        flush(&mut contract);
+
+        // Reload from storage so nothing is answered from the in-memory caches.
+        let contract = FractalRegistry::default();
+        let grant_id = contract.grant_ids_by_owner.get(&owner).unwrap()[0].clone();
+
+        assert!(contract.grants_by_id.contains_key(&grant_id));
+        assert!(!contract.grant_ids_by_data_id.contains_key(&grant_id));
+        assert!(!contract.grant_ids_by_owner.contains_key(&grantee));
+        assert!(!contract.grant_ids_by_grantee.contains_key(&owner));
+        assert_eq!(
+            contract.grant_ids_by_grantee.get(&grantee),
+            Some(&vec![grant_id.clone()])
+        );
+        assert_eq!(
+            contract.grant_ids_by_data_id.get("A1"),
+            Some(&vec![grant_id])
+        );
+    }
+
+    #[test]
+    fn find_grants_full_includes_ids_and_liveness() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), Some(NOW + 1));
+        contract.insert_grant(public_key(2), "A3".into(), Some(NOW - 1));
+
+        let entries = contract.find_grants_full(Some(owner.clone()), None, None, None, None);
+        let liveness: Vec<(String, bool)> = entries
+            .iter()
+            .map(|entry| (entry.grant.data_id.clone(), entry.is_timelocked))
+            .collect();
+        assert_eq!(
+            liveness,
+            vec![
+                ("A1".into(), false),
+                ("A2".into(), true),
+                ("A3".into(), false)
+            ]
+        );
+        assert!(entries
+            .iter()
+            .all(|entry| entry.grant_id == derive_grant_id(&entry.grant)));
+
+        let page = contract.find_grants_full(Some(owner), None, None, Some(1), Some(1));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].grant_id, entries[1].grant_id);
+    }
+
+    #[test]
+    fn data_ids_for_grantee_lists_each_data_id_once() {
+        let grantee = public_key(3);
+        let mut contract = FractalRegistry::default();
+
+        signed_by(&public_key(1));
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        contract.insert_grant(grantee.clone(), "A2".into(), None);
+        signed_by(&public_key(2));
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+
+        assert_eq!(
+            contract.data_ids_for_grantee(grantee.clone(), 0, 10),
+            vec!["A1", "A2"]
+        );
+        assert_eq!(
+            contract.data_ids_for_grantee(grantee.clone(), 1, 2),
+            vec!["A2", "A1"]
+        );
+        assert!(contract.data_ids_for_grantee(grantee, 3, 10).is_empty());
+        assert!(contract
+            .data_ids_for_grantee(public_key(4), 0, 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn find_grants_projected_only_serializes_requested_fields() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW + 1));
+        contract.insert_grant_with_options(
+            public_key(3),
+            "A2".into(),
+            None,
+            GrantOptions {
+                expires_at: Some(NOW + 10),
+                label: Some("kyc".into()),
+                required_attestor: Some("attestor.near".parse().unwrap()),
+                valid_from: Some(NOW + 5),
+                immutable: true,
+                co_owners: vec![public_key(4)],
+                ..GrantOptions::default()
+            },
+        );
+
+        let projected = contract.find_grants_projected(
+            Some(owner.clone()),
+            None,
+            None,
+            Some(vec![GrantField::Owner, GrantField::DataId]),
+        );
+        assert_eq!(
+            serde_json::to_value(projected).unwrap(),
+            serde_json::json!([
+                {"owner": owner, "data_id": "A1"},
+                {"owner": owner, "data_id": "A2"}
+            ])
+        );
+
+        let full = serde_json::to_value(contract.find_grants_projected(
+            Some(owner.clone()),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(full[1]["label"], "kyc");
+        assert_eq!(full[1]["co_owners"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            full,
+            serde_json::to_value(contract.find_grants(Some(owner), None, None, None, None))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn self_grants_are_allowed_by_default() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(owner.clone(), "A1".into(), None);
+
+        assert!(contract.is_granted(owner.clone(), owner, "A1".into()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Grantee can't be the owner")]
+    fn self_grants_are_rejected_in_strict_mode() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_reject_self_grant(true);
+
+        signed_by(&owner);
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(owner, "A1".into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract account can do this")]
+    fn only_the_contract_can_set_reject_self_grant() {
+        signed_by(&public_key(1));
+
+        FractalRegistry::default().set_reject_self_grant(true);
+    }
+
+    #[test]
+    fn migrate_keeps_legacy_grants() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut legacy = FractalRegistry::default();
+        legacy.insert_grant(public_key(2), "A1".into(), None);
+        env::state_write(&LegacyStateFixture {
+            grants_by_id: legacy.grants_by_id,
+            grant_ids_by_owner: legacy.grant_ids_by_owner,
+            grant_ids_by_grantee: legacy.grant_ids_by_grantee,
+            grant_ids_by_data_id: legacy.grant_ids_by_data_id,
+        });
+
+        let migrated = FractalRegistry::migrate();
+
+        assert!(migrated.is_granted(owner, public_key(2), "A1".into()));
+        assert!(!migrated.reject_self_grant);
+        assert!(migrated.find_grants_by_label(None, "kyc".into()).is_empty());
+        assert_eq!(
+            migrated.distinct_grantees(),
+            0,
+            "only counts forward from the upgrade"
+        );
+        assert!(migrated.all_data_ids(0, 10).is_empty());
+
+        let mut migrated = migrated;
+        called_by_contract();
+        assert_eq!(
+            migrated.backfill_data_ids(vec!["A1".into(), "A1".into(), "B1".into()]),
+            1
+        );
+        assert_eq!(migrated.all_data_ids(0, 10), vec!["A1"]);
+        assert!(migrated.all_data_ids(u64::MAX, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "limit must be at most 100")]
+    fn all_data_ids_caps_the_limit() {
+        FractalRegistry::default().all_data_ids(0, MAX_RESULTS as u64 + 1);
+    }
+
+    #[derive(BorshSerialize)]
+    struct LegacyStateFixture {
+        grants_by_id: LookupMap<String, Grant>,
+        grant_ids_by_owner: LookupMap<PublicKey, Vec<String>>,
+        grant_ids_by_grantee: LookupMap<PublicKey, Vec<String>>,
+        grant_ids_by_data_id: LookupMap<String, Vec<String>>,
+    }
+
+    #[test]
+    fn storage_usage_follows_inserts_and_deletes() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        let initial = contract.storage_usage();
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        flush(&mut contract);
+        let after_insert = contract.storage_usage();
+
+        contract.delete_grant(public_key(2), "A1".into(), None);
+        flush(&mut contract);
+        let after_delete = contract.storage_usage();
+
+        assert!(after_insert > initial);
+        assert!(after_delete < after_insert);
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's grants are frozen")]
+    fn purge_expired_grants_is_rejected_for_frozen_owners() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW + 10),
+            GrantOptions {
+                expires_at: Some(NOW + 1),
+                ..GrantOptions::default()
+            },
+        );
+        contract.freeze_own_grants();
+
+        signed_by_at(&owner, NOW + 1);
+        contract.purge_expired_grants(10);
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn purge_expired_grants_is_rejected_while_paused() {
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_paused(true);
+
+        signed_by(&public_key(1));
+        contract.purge_expired_grants(10);
+    }
+
+    #[test]
+    fn purge_expired_grants_announces_each_expiry_once() {
+        let owner = public_key(1);
+        let expiring = GrantOptions {
+            expires_at: Some(NOW + 10),
+            ..GrantOptions::default()
+        };
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant_with_options(public_key(2), "A1".into(), None, expiring.clone());
+        contract.insert_grant_with_options(public_key(3), "A1".into(), Some(NOW + 20), expiring);
+        contract.insert_grant(public_key(4), "A1".into(), None);
+
+        signed_by_at(&owner, NOW + 10);
+        assert_eq!(contract.purge_expired_grants(10), 1);
+        let events: Vec<Value> = logged_events();
+        let event_names: Vec<&str> = events
+            .iter()
+            .map(|event| event["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            event_names,
+            vec!["grant_expired", "grant_deleted", "grant_expired"]
+        );
+
+        signed_by_at(&owner, NOW + 15);
+        assert_eq!(contract.purge_expired_grants(10), 0);
+        assert!(get_logs().is_empty());
+
+        signed_by_at(&owner, NOW + 21);
+        assert_eq!(contract.purge_expired_grants(10), 1);
+        assert_eq!(logged_events().len(), 1);
+        assert_eq!(logged_events()[0]["event"], "grant_deleted");
+
+        let remaining = contract.find_grants(Some(owner), None, None, None, None);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].grantee, public_key(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant would already be expired")]
+    fn grants_cant_be_inserted_already_expired() {
+        signed_by(&public_key(1));
+
+        FractalRegistry::default().insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions {
+                expires_at: Some(NOW),
+                ..GrantOptions::default()
+            },
+        );
+    }
+
+    #[test]
+    fn grants_stored_before_new_fields_still_deserialize() {
+        #[derive(BorshSerialize)]
+        struct LegacyGrant {
+            owner: PublicKey,
+            grantee: PublicKey,
+            data_id: String,
+            locked_until: EpochHeight,
+        }
+
+        let bytes = borsh::to_vec(&LegacyGrant {
+            owner: public_key(1),
+            grantee: public_key(2),
+            data_id: "A1".into(),
+            locked_until: 1337,
+        })
+        .unwrap();
+        let grant: Grant = borsh::from_slice(&bytes).unwrap();
+
+        assert_eq!(grant.data_id, "A1");
+        assert_eq!(grant.locked_until, 1337);
+        assert_eq!(grant.expires_at, None);
+        assert!(!grant.expired);
+        assert_eq!(grant.label, None);
+
+        let roundtrip: Grant = borsh::from_slice(&borsh::to_vec(&grant).unwrap()).unwrap();
+        assert_eq!(derive_grant_id(&roundtrip), derive_grant_id(&grant));
+    }
+
+    #[test]
+    fn has_grants_on_all_requires_every_data_id() {
+        let grantee = public_key(3);
+        let required: Vec<String> = vec!["A1".into(), "A2".into(), "A3".into()];
+        let mut contract = FractalRegistry::default();
+
+        signed_by(&public_key(1));
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        signed_by(&public_key(2));
+        contract.insert_grant(grantee.clone(), "A2".into(), None);
+        contract.insert_grant(public_key(4), "A3".into(), None);
+
+        assert!(!contract.has_grants_on_all(grantee.clone(), required.clone()));
+
+        contract.insert_grant(grantee.clone(), "A3".into(), None);
+
+        assert!(contract.has_grants_on_all(grantee, required));
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch is larger than MAX_BATCH_SIZE (32)")]
+    fn has_grants_on_all_bounds_the_data_ids() {
+        signed_by(&public_key(1));
+
+        FractalRegistry::default().has_grants_on_all(
+            public_key(2),
+            (0..=MAX_BATCH_SIZE).map(|i| i.to_string()).collect(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant is timelocked")]
+    fn timelocked_grants_cant_be_deleted_without_override() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW + 1));
+
+        contract.delete_grant(public_key(2), "A1".into(), None);
+    }
+
+    #[test]
+    fn timelock_override_allows_deleting_timelocked_grants() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_timelock_override(true);
+
+        signed_by(&owner);
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW + 1));
+        contract.insert_grant(public_key(2), "A2".into(), None);
+        contract.delete_grant(public_key(2), "A1".into(), None);
+        contract.delete_grant(public_key(2), "A2".into(), None);
+
+        let event_names: Vec<String> = logged_events()
+            .iter()
+            .skip(2)
+            .map(|event| event["event"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            event_names,
+            vec![
+                "grant_timelock_overridden",
+                "grant_deleted",
+                "grant_deleted"
+            ]
+        );
+        assert!(contract
+            .find_grants(Some(owner), None, None, None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn find_grants_page_doesnt_skip_when_grants_change_between_pages() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        for data_id in ["A1", "A2", "A3", "A4"] {
+            contract.insert_grant(public_key(2), data_id.into(), None);
+        }
+
+        let page_data_ids = |page: &GrantsPage| -> Vec<String> {
+            page.grants
+                .iter()
+                .map(|grant| grant.data_id.clone())
+                .collect()
+        };
+
+        let first = contract.find_grants_page(Some(owner.clone()), None, None, None, 2);
+        assert_eq!(page_data_ids(&first), vec!["A1", "A2"]);
+
+        contract.delete_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A5".into(), None);
+
+        let second =
+            contract.find_grants_page(Some(owner.clone()), None, None, first.next_cursor, 2);
+        assert_eq!(page_data_ids(&second), vec!["A3", "A4"]);
+
+        let third = contract.find_grants_page(Some(owner), None, None, second.next_cursor, 2);
+        assert_eq!(page_data_ids(&third), vec!["A5"]);
+        assert_eq!(third.next_cursor, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cursor is no longer valid")]
+    fn find_grants_page_rejects_a_deleted_cursor() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), None);
+
+        let first = contract.find_grants_page(Some(owner.clone()), None, None, None, 1);
+        contract.delete_grant(public_key(2), "A1".into(), None);
+
+        contract.find_grants_page(Some(owner), None, None, first.next_cursor, 1);
+    }
+
+    #[test]
+    fn revoke_all_deletes_every_locked_until() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW - 1));
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW + 1));
+        contract.insert_grant(public_key(2), "A2".into(), None);
+
+        assert_eq!(contract.revoke_all(public_key(2), "A1".into()), 2);
+
+        let remaining: Vec<(String, EpochHeight)> = contract
+            .find_grants(Some(owner), None, None, None, None)
+            .into_iter()
+            .map(|grant| (grant.data_id, grant.locked_until))
+            .collect();
+        assert_eq!(remaining, vec![("A1".into(), NOW + 1), ("A2".into(), 0)]);
+    }
+
+    #[test]
+    fn grants_accept_every_supported_curve() {
+        let owner = public_key(1);
+        let secp256k1 = PublicKey::from_parts(CurveType::SECP256K1, vec![7; 64]).unwrap();
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(secp256k1.clone(), "A1".into(), None);
+
+        assert!(contract.is_granted(owner.clone(), public_key(2), "A1".into()));
+        assert!(contract.is_granted(owner, secp256k1, "A1".into()));
+        // Unsupported curves never make it into a `PublicKey`, so `require_supported_curve` is a backstop.
+        assert!(PublicKey::try_from([vec![2], vec![7; 32]].concat()).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Grantee can't be the owner")]
+    fn rotate_grantee_key_rejects_self_grants_in_strict_mode() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_reject_self_grant(true);
+
+        signed_by(&owner);
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.rotate_grantee_key(public_key(2), owner);
+    }
+
+    #[test]
+    fn rotate_grantee_key_moves_all_of_the_owners_grants() {
+        let owner = public_key(1);
+        let (old_grantee, new_grantee) = (public_key(2), public_key(3));
+        let mut contract = FractalRegistry::default();
+        signed_by(&public_key(9));
+        contract.insert_grant(old_grantee.clone(), "A1".into(), None);
+        signed_by(&owner);
+        contract.insert_grant(old_grantee.clone(), "A1".into(), None);
+        contract.insert_grant(old_grantee.clone(), "A2".into(), Some(NOW + 1));
+
+        assert_eq!(
+            contract.rotate_grantee_key(old_grantee.clone(), new_grantee.clone()),
+            2
+        );
+
+        let rotated: Vec<(PublicKey, String, EpochHeight)> = contract
+            .find_grants(Some(owner.clone()), None, None, None, None)
+            .into_iter()
+            .map(|grant| (grant.grantee, grant.data_id, grant.locked_until))
+            .collect();
+        assert_eq!(
+            rotated,
+            vec![
+                (new_grantee.clone(), "A1".into(), 0),
+                (new_grantee.clone(), "A2".into(), NOW + 1),
+            ]
+        );
+        assert_eq!(
+            contract
+                .find_grants(None, Some(old_grantee), None, None, None)
+                .len(),
+            1
+        );
+        assert_eq!(
+            contract
+                .find_grants(None, Some(new_grantee), Some("A1".into()), None, None)
+                .len(),
+            1
+        );
+        let event = logged_events().last().unwrap().clone();
+        assert_eq!(event["event"], "grantee_rotated");
+        let grant_id = |field: &str| event["data"][field].as_str().unwrap().to_string();
+        assert!(contract.get_grant(grant_id("old_grant_id")).is_none());
+        assert_eq!(
+            contract
+                .get_grant(grant_id("new_grant_id"))
+                .unwrap()
+                .grantee,
+            public_key(3)
+        );
+    }
+
+    #[test]
+    fn owners_for_grantee_lists_each_owner_once() {
+        let grantee = public_key(3);
+        let mut contract = FractalRegistry::default();
+
+        signed_by(&public_key(1));
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        contract.insert_grant(grantee.clone(), "A2".into(), None);
+        signed_by(&public_key(2));
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+
+        assert_eq!(
+            contract.owners_for_grantee(grantee.clone(), 0, 10),
+            vec![public_key(1), public_key(2)]
+        );
+        assert_eq!(
+            contract.owners_for_grantee(grantee, 2, 10),
+            vec![public_key(2)]
+        );
+    }
+
+    fn labeled(label: &str) -> GrantOptions {
+        GrantOptions {
+            label: Some(label.into()),
+            ..GrantOptions::default()
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "More than 100 grants match, use find_grants_page or pass a limit")]
+    fn find_grants_by_label_asks_to_paginate_past_max_results() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        for i in 0..=MAX_RESULTS {
+            signed_by(&owner);
+            contract.insert_grant_with_options(public_key(2), i.to_string(), None, labeled("kyc"));
+        }
+
+        contract.find_grants_by_label(Some(owner), "kyc".into());
+    }
+
+    #[test]
+    fn find_grants_by_label_follows_inserts_and_deletes() {
+        let (alice, bob) = (public_key(1), public_key(2));
+        let mut contract = FractalRegistry::default();
+        signed_by(&alice);
+        contract.insert_grant_with_options(public_key(3), "A1".into(), None, labeled("kyc"));
+        contract.insert_grant_with_options(public_key(3), "A2".into(), None, labeled("tax-2024"));
+        contract.insert_grant(public_key(4), "A1".into(), None);
+        signed_by(&bob);
+        contract.insert_grant_with_options(public_key(3), "B1".into(), None, labeled("kyc"));
+
+        let data_ids = |grants: Vec<Grant>| -> Vec<String> {
+            grants.into_iter().map(|grant| grant.data_id).collect()
+        };
+        assert_eq!(
+            data_ids(contract.find_grants_by_label(None, "kyc".into())),
+            vec!["A1", "B1"]
+        );
+        assert_eq!(
+            data_ids(contract.find_grants_by_label(Some(alice.clone()), "kyc".into())),
+            vec!["A1"]
+        );
+
+        signed_by(&alice);
+        contract.delete_grant(public_key(3), "A1".into(), None);
+
+        assert_eq!(
+            data_ids(contract.find_grants_by_label(None, "kyc".into())),
+            vec!["B1"]
+        );
+        assert_eq!(
+            contract.find_grants(Some(alice), Some(public_key(3)), None, None, None)[0].label,
+            Some("tax-2024".into())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "label must be 1 to 64 bytes long")]
+    fn labels_are_capped() {
+        signed_by(&public_key(1));
+
+        FractalRegistry::default().insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            labeled(&"x".repeat(MAX_LABEL_LEN + 1)),
+        );
+    }
+
+    fn fields_of(grant: &Grant) -> GrantFields {
+        GrantFields {
+            owner: grant.owner.clone(),
+            grantee: grant.grantee.clone(),
+            data_id: grant.data_id.clone(),
+            locked_until: grant.locked_until,
+            expires_at: grant.expires_at,
+            label: grant.label.clone(),
+        }
+    }
+
+    #[test]
+    fn delete_grant_checked_deletes_a_matching_grant() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(public_key(2), "A1".into(), None, labeled("kyc"));
+        contract.insert_grant(public_key(2), "A2".into(), None);
+        let grant = contract.find_grants(Some(owner.clone()), None, Some("A1".into()), None, None)
+            [0]
+        .clone();
+
+        contract.delete_grant_checked(derive_grant_id(&grant), fields_of(&grant));
+
+        let remaining = contract.find_grants(Some(owner), None, None, None, None);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].data_id, "A2");
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant doesn't match the expected fields")]
+    fn delete_grant_checked_rejects_a_mismatch() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(public_key(2), "A1".into(), None, labeled("kyc"));
+        let grant = contract.find_grants(Some(owner), None, None, None, None)[0].clone();
+
+        contract.delete_grant_checked(
+            derive_grant_id(&grant),
+            GrantFields {
+                label: Some("tax-2024".into()),
+                ..fields_of(&grant)
+            },
+        );
+    }
+
+    #[test]
+    fn estimate_insert_cost_matches_the_actual_storage_used() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        for (grantee, data_id) in [
+            (public_key(2), "A1"),
+            (public_key(2), "A2"),
+            (public_key(3), "A2"),
+        ] {
+            let estimate =
+                contract.estimate_insert_cost(owner.clone(), grantee.clone(), data_id.into(), None);
+            let before = contract.storage_usage();
+
+            contract.insert_grant(grantee, data_id.into(), None);
+            flush(&mut contract);
+
+            assert_eq!(estimate.storage_bytes, contract.storage_usage() - before);
+            assert_eq!(
+                estimate.storage_cost,
+                env::storage_byte_cost().saturating_mul(estimate.storage_bytes as u128)
+            );
+        }
+    }
+
+    #[test]
+    fn estimate_insert_cost_counts_the_data_id_claim() {
+        let owner = public_key(1);
+        let mut contract = with_data_id_claims();
+        signed_by(&owner);
+
+        for (grantee, data_id) in [(public_key(2), "A1"), (public_key(3), "A1")] {
+            let estimate =
+                contract.estimate_insert_cost(owner.clone(), grantee.clone(), data_id.into(), None);
+            let before = contract.storage_usage();
+
+            contract.insert_grant(grantee, data_id.into(), None);
+            flush(&mut contract);
+
+            assert_eq!(estimate.storage_bytes, contract.storage_usage() - before);
+        }
+    }
+
+    #[test]
+    fn find_grants_can_exclude_a_grantee() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        for grantee in [public_key(2), public_key(3), public_key(4)] {
+            contract.insert_grant(grantee, "A1".into(), None);
+        }
+
+        let grantees: Vec<PublicKey> = contract
+            .find_grants(
+                Some(owner),
+                None,
+                Some("A1".into()),
+                Some(public_key(3)),
+                None,
+            )
+            .into_iter()
+            .map(|grant| grant.grantee)
+            .collect();
+
+        assert_eq!(grantees, vec![public_key(2), public_key(4)]);
+    }
+
+    fn called_by(account_id: &str) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .block_timestamp(NOW)
+            .build());
+    }
+
+    #[test]
+    fn grants_requiring_attestation_are_inactive_until_attested() {
+        let owner = public_key(1);
+        let grantee = public_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            grantee.clone(),
+            "A1".into(),
+            None,
+            GrantOptions {
+                required_attestor: Some("attestor.near".parse().unwrap()),
+                ..GrantOptions::default()
+            },
+        );
+        contract.insert_grant(public_key(3), "A1".into(), None);
+        let grant_id =
+            contract._find_grant_ids(Some(owner), Some(grantee.clone()), None)[0].clone();
+
+        assert!(contract
+            .active_grants_for(grantee.clone(), "A1".into())
+            .is_empty());
+        assert_eq!(
+            contract.active_grants_for(public_key(3), "A1".into()).len(),
+            1
+        );
+
+        called_by("attestor.near");
+        contract.attest(grant_id);
+
+        assert_eq!(contract.active_grants_for(grantee, "A1".into()).len(), 1);
+        assert_eq!(logged_events()[0]["event"], "grant_attested");
+        assert_eq!(logged_events()[0]["data"]["attestor"], "attestor.near");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the grant's required attestor can attest it")]
+    fn only_the_required_attestor_can_attest() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions {
+                required_attestor: Some("attestor.near".parse().unwrap()),
+                ..GrantOptions::default()
+            },
+        );
+        let grant_id = contract._find_grant_ids(Some(owner), None, None)[0].clone();
+
+        called_by("someone-else.near");
+        contract.attest(grant_id);
+    }
+
+    #[test]
+    fn get_grants_returns_results_aligned_with_the_ids() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), None);
+        let a1 = contract._find_grant_ids(Some(owner.clone()), None, Some("A1".into()))[0].clone();
+        let a2 = contract._find_grant_ids(Some(owner), None, Some("A2".into()))[0].clone();
+
+        let grants = contract.get_grants(vec![a2, "missing".into(), a1]);
+
+        assert_eq!(grants.len(), 3);
+        assert_eq!(grants[0].as_ref().unwrap().data_id, "A2");
+        assert!(grants[1].is_none());
+        assert_eq!(grants[2].as_ref().unwrap().data_id, "A1");
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch is larger than MAX_BATCH_SIZE (32)")]
+    fn get_grants_bounds_the_grant_ids() {
+        let contract = FractalRegistry::default();
+        contract.get_grants(vec!["id".into(); MAX_BATCH_SIZE + 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "data_id must not be empty")]
+    fn insert_grant_rejects_an_empty_data_id() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "".into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "data_id must be at most 256 bytes long")]
+    fn insert_grant_rejects_an_over_long_data_id() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "x".repeat(MAX_DATA_ID_LEN + 1), None);
+    }
+
+    #[test]
+    fn data_ids_up_to_max_data_id_len_are_accepted_everywhere() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let longest = "x".repeat(MAX_DATA_ID_LEN);
+
+        contract.insert_grant(public_key(2), longest.clone(), None);
+        contract.insert_grant_multi(public_key(3), vec![longest.clone()], None, None);
+        contract.insert_grant_to_many(vec![public_key(4)], longest.clone(), None, None);
+
+        assert_eq!(
+            contract
+                .find_grant_ids(Some(owner), None, Some(longest))
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "data_id must be at most 256 bytes long")]
+    fn batch_inserts_reject_an_over_long_data_id() {
+        signed_by(&public_key(1));
+        FractalRegistry::default().insert_grant_to_many(
+            vec![public_key(2)],
+            "x".repeat(MAX_DATA_ID_LEN + 1),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn owners_default_timelock_applies_when_locked_until_is_omitted() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.set_default_timelock(Some(1_000));
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), Some(5));
+
+        let grants = contract._find_grants(Some(owner), None, None);
+        assert_eq!(grants[0].locked_until, NOW + 1_000);
+        assert_eq!(grants[1].locked_until, 5);
+        assert_eq!(logged_events()[0]["data"]["locked_until"], NOW + 1_000);
+    }
+
+    #[test]
+    fn grants_arent_locked_without_a_default_timelock() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.set_default_timelock(Some(1_000));
+        contract.set_default_timelock(None);
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        signed_by(&public_key(3));
+        contract.insert_grant(public_key(2), "A1".into(), None);
+
+        for grant in contract._find_grants(None, Some(public_key(2)), None) {
+            assert_eq!(grant.locked_until, 0);
+        }
+    }
+
+    #[test]
+    fn pause_status_records_the_last_set_paused_call() {
+        let mut contract = FractalRegistry::default();
+        let status = contract.pause_status();
+        assert!(!status.paused);
+        assert!(status.changed_by.is_none());
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(env::current_account_id())
+            .block_height(42)
+            .build());
+        contract.set_paused(true);
+
+        let status = contract.pause_status();
+        assert!(status.paused);
+        assert_eq!(status.changed_by, Some(env::current_account_id()));
+        assert_eq!(status.changed_at, Some(42));
+
+        called_by_contract();
+        contract.set_paused(false);
+        assert!(!contract.pause_status().paused);
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn grants_cant_be_inserted_while_paused() {
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_paused(true);
+
+        signed_by(&public_key(1));
+        contract.insert_grant(public_key(2), "A1".into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract account can do this")]
+    fn only_the_contract_account_can_pause() {
+        let mut contract = FractalRegistry::default();
+        signed_by(&public_key(1));
+        contract.set_paused(true);
+    }
+
+    #[test]
+    fn find_grants_grouped_buckets_grants_by_data_id() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), None);
+        contract.insert_grant(public_key(3), "A1".into(), None);
+
+        let groups = contract.find_grants_grouped(Some(owner), None);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "A1");
+        assert_eq!(groups[0].1.len(), 2);
+        assert!(groups[0].1.iter().all(|grant| grant.data_id == "A1"));
+        assert_eq!(groups[1].0, "A2");
+        assert_eq!(groups[1].1.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch is larger than MAX_BATCH_SIZE (32)")]
+    fn insert_grant_multi_bounds_the_data_ids() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant_multi(
+            public_key(2),
+            (0..=MAX_BATCH_SIZE).map(|i| i.to_string()).collect(),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn insert_and_get_grant_returns_the_stored_grant() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        let grant = contract.insert_and_get_grant(public_key(2), "A1".into(), None);
+
+        assert_eq!(grant.locked_until, 0);
+        assert_eq!(grant.owner, owner);
+        let stored = contract.get_grant(derive_grant_id(&grant)).unwrap();
+        assert_eq!(
+            serde_json::to_value(&grant).unwrap(),
+            serde_json::to_value(&stored).unwrap()
+        );
+    }
+
+    // Re-signing every insert keeps each call's event logs under the mock's log limit.
+    fn insert_many(contract: &mut FractalRegistry, owner: &PublicKey, count: usize) {
+        for i in 0..count {
+            signed_by(owner);
+            contract.insert_grant(public_key(2), i.to_string(), None);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "More than 100 grants match, use find_grants_page or pass a limit")]
+    fn find_grants_asks_to_paginate_past_max_results() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        insert_many(&mut contract, &owner, MAX_RESULTS + 1);
+
+        contract.find_grants(Some(owner), None, None, None, None);
+    }
+
+    #[test]
+    fn find_grants_full_doesnt_cap_limited_queries() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        insert_many(&mut contract, &owner, MAX_RESULTS + 1);
+
+        let page = contract.find_grants_full(Some(owner.clone()), None, None, None, Some(10));
+        assert_eq!(page.len(), 10);
+        let rest = contract.find_grants_full(Some(owner), None, None, Some(10), None);
+        assert_eq!(rest.len(), MAX_RESULTS - 9);
+    }
+
+    #[test]
+    fn find_grants_created_between_filters_on_created_at() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        for (i, at) in [NOW, NOW + 10, NOW + 20].iter().enumerate() {
+            signed_by_at(&owner, *at);
+            contract.insert_grant(public_key(2), i.to_string(), None);
+        }
+
+        let grants = contract.find_grants_created_between(owner, NOW + 5, NOW + 20);
+
+        assert_eq!(grants.len(), 2);
+        assert_eq!(grants[0].created_at, Some(NOW + 10));
+        assert_eq!(grants[1].created_at, Some(NOW + 20));
+    }
+
+    #[test]
+    fn deleting_tolerates_a_missing_index_entry() {
+        let owner = public_key(1);
+        let grantee = public_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        contract.grant_ids_by_grantee.remove(&grantee);
+
+        assert_eq!(contract.delete_grants_for_data_id("A1".into()), 1);
+        assert!(contract._find_grants(Some(owner), None, None).is_empty());
+    }
+
+    #[test]
+    fn grants_arent_active_before_valid_from() {
+        let owner = public_key(1);
+        let (grantee_sk, grantee) = signing_key(2);
+        let message = b"I am the grantee".to_vec();
+        let signature = grantee_sk.sign(&message).to_bytes().to_vec();
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            grantee.clone(),
+            "A1".into(),
+            None,
+            GrantOptions {
+                valid_from: Some(NOW + 10),
+                ..GrantOptions::default()
+            },
+        );
+
+        assert!(contract
+            .active_grants_for(grantee.clone(), "A1".into())
+            .is_empty());
+        assert!(!contract.is_granted(owner.clone(), grantee.clone(), "A1".into()));
+        assert!(!contract.verify_grant_with_signature(
+            grantee.clone(),
+            "A1".into(),
+            message.clone(),
+            signature.clone(),
+        ));
+
+        signed_by_at(&owner, NOW + 10);
+        assert_eq!(
+            contract
+                .active_grants_for(grantee.clone(), "A1".into())
+                .len(),
+            1
+        );
+        assert!(contract.is_granted(owner, grantee.clone(), "A1".into()));
+        assert!(contract.verify_grant_with_signature(grantee, "A1".into(), message, signature));
+    }
+
+    #[test]
+    fn which_exist_is_aligned_with_the_ids() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        let grant_id = contract._find_grant_ids(Some(owner), None, None)[0].clone();
+
+        assert_eq!(
+            contract.which_exist(vec!["missing".into(), grant_id, "gone".into()]),
+            vec![false, true, false]
+        );
+    }
+
+    #[test]
+    fn grant_ids_for_owner_paged_returns_consecutive_chunks() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        insert_many(&mut contract, &owner, 25);
+
+        let mut paged = vec![];
+        for from_index in (0..30).step_by(10) {
+            let chunk = contract.grant_ids_for_owner_paged(owner.clone(), from_index, 10);
+            assert!(chunk.len() <= 10);
+            paged.extend(chunk);
+        }
+
+        assert_eq!(paged, *contract.grant_ids_by_owner.get(&owner).unwrap());
+        assert!(contract
+            .grant_ids_for_owner_paged(owner.clone(), 25, 10)
+            .is_empty());
+        assert!(contract
+            .grant_ids_for_owner_paged(owner, u64::MAX, 10)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "limit must be at most 100")]
+    fn grant_ids_for_owner_paged_caps_the_limit() {
+        FractalRegistry::default().grant_ids_for_owner_paged(
+            public_key(1),
+            0,
+            MAX_RESULTS as u64 + 1,
+        );
+    }
+
+    fn with_idempotency_key(key: &str) -> GrantOptions {
+        GrantOptions {
+            idempotency_key: Some(key.into()),
+            ..GrantOptions::default()
+        }
+    }
+
+    #[test]
+    fn retried_inserts_with_the_same_idempotency_key_are_no_ops() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        let first = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+        let second = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+
+        assert_eq!(first, second);
+        assert_eq!(contract._find_grants(Some(owner), None, None).len(), 1);
+        assert_eq!(logged_events().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant already exists")]
+    fn idempotency_keys_expire() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+
+        signed_by_at(&public_key(1), NOW + IDEMPOTENCY_KEY_TTL);
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "idempotency_key was already used for another grantee or data_id")]
+    fn idempotency_keys_cant_be_reused_for_another_grant() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A2".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn retries_with_an_idempotency_key_still_fail_while_paused() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+
+        called_by_contract();
+        contract.set_paused(true);
+        signed_by(&owner);
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+    }
+
+    #[test]
+    fn retries_after_the_grant_was_deleted_insert_it_again() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+        contract.delete_grant(public_key(2), "A1".into(), None);
+
+        let grant_id = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+
+        assert!(contract.get_grant(grant_id).is_some());
+    }
+
+    #[test]
+    fn expired_idempotency_keys_are_removed() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        for (data_id, key) in [("A1", "retry-1"), ("A2", "retry-2"), ("A3", "retry-3")] {
+            contract.insert_grant_with_options(
+                public_key(2),
+                data_id.into(),
+                None,
+                with_idempotency_key(key),
+            );
+        }
+        let remembered = |contract: &FractalRegistry, key: &str| {
+            contract
+                .idempotency_keys
+                .contains_key(&(owner.clone(), key.to_string()))
+        };
+
+        signed_by_at(&owner, NOW + IDEMPOTENCY_KEY_TTL);
+        assert_eq!(contract.purge_expired_idempotency_keys(1), 1);
+        assert!(!remembered(&contract, "retry-1"));
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A4".into(),
+            None,
+            with_idempotency_key("retry-4"),
+        );
+
+        assert!(!remembered(&contract, "retry-2"));
+        assert!(!remembered(&contract, "retry-3"));
+        assert!(remembered(&contract, "retry-4"));
+        assert_eq!(contract.purge_expired_idempotency_keys(10), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch is larger than MAX_BATCH_SIZE (32)")]
+    fn purge_expired_idempotency_keys_rejects_a_limit_that_truncates_into_range() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+
+        contract.purge_expired_idempotency_keys((1 << 32) + 1);
+    }
+
+    #[test]
+    fn grant_time_bounds_spans_the_owners_grants() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        signed_by(&owner);
+        assert_eq!(contract.grant_time_bounds(owner.clone(), 0, 10), None);
+
+        for (i, at) in [NOW + 10, NOW, NOW + 20].iter().enumerate() {
+            signed_by_at(&owner, *at);
+            contract.insert_grant(public_key(2), i.to_string(), None);
+        }
+
+        assert_eq!(
+            contract.grant_time_bounds(owner.clone(), 0, 10),
+            Some((NOW, NOW + 20))
+        );
+        assert_eq!(
+            contract.grant_time_bounds(owner.clone(), 0, 2),
+            Some((NOW, NOW + 10))
+        );
+        assert_eq!(
+            contract.grant_time_bounds(owner.clone(), 2, 2),
+            Some((NOW + 20, NOW + 20))
+        );
+        assert_eq!(contract.grant_time_bounds(owner, 3, 2), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "limit must be at most 100")]
+    fn grant_time_bounds_caps_the_limit() {
+        signed_by(&public_key(1));
+        FractalRegistry::default().grant_time_bounds(public_key(1), 0, MAX_RESULTS as u64 + 1);
+    }
+
+    #[test]
+    fn find_grants_handles_emptied_indexes() {
+        let owner = public_key(1);
+        let other_owner = public_key(3);
+        let grantee = public_key(2);
+        let other_grantee = public_key(4);
+        let mut contract = FractalRegistry::default();
+        signed_by(&owner);
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        contract.delete_grant(grantee.clone(), "A1".into(), None);
+        contract.insert_grant(other_grantee.clone(), "A2".into(), None);
+        contract.delete_grant(other_grantee.clone(), "A2".into(), None);
+        signed_by(&other_owner);
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        assert!(contract.grant_ids_by_owner.get(&owner).unwrap().is_empty());
+        assert!(contract
+            .grant_ids_by_grantee
+            .get(&other_grantee)
+            .unwrap()
+            .is_empty());
+
+        // Empty head.
+        assert!(contract
+            .find_grants(Some(owner.clone()), Some(grantee.clone()), None, None, None)
+            .is_empty());
+        // Empty tail.
+        assert!(contract
+            .find_grants(
+                Some(other_owner),
+                Some(other_grantee.clone()),
+                None,
+                None,
+                None
+            )
+            .is_empty());
+        // All empty.
+        assert!(contract
+            .find_grants(
+                Some(owner),
+                Some(other_grantee),
+                Some("A2".into()),
+                None,
+                None
+            )
+            .is_empty());
+        assert_eq!(
+            contract
+                .find_grants(None, Some(grantee), None, None, None)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's grants are frozen")]
+    fn frozen_owners_cant_insert() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.freeze_own_grants();
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's grants are frozen")]
+    fn frozen_owners_cant_delete() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.freeze_own_grants();
+
+        contract.delete_grant(public_key(2), "A1".into(), None);
+    }
+
+    #[test]
+    fn freezing_only_affects_the_owner_until_unfrozen() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.freeze_own_grants();
+
+        signed_by(&public_key(3));
+        contract.insert_grant(public_key(2), "A1".into(), None);
+
+        signed_by(&owner);
+        contract.unfreeze_own_grants();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+
+        let events = logged_events();
+        assert_eq!(events[0]["event"], "owner_unfrozen");
+        assert_eq!(
+            events[0]["data"]["owner"],
+            serde_json::to_value(&owner).unwrap()
+        );
+        assert_eq!(
+            contract._find_grants(None, Some(public_key(2)), None).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn find_grants_all_data_ids_requires_every_data_id() {
+        let grantee = public_key(2);
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        contract.insert_grant(grantee.clone(), "A2".into(), None);
+        contract.insert_grant(grantee.clone(), "A3".into(), None);
+
+        let both =
+            contract.find_grants_all_data_ids(grantee.clone(), vec!["A1".into(), "A2".into()]);
+        assert_eq!(
+            both.iter()
+                .map(|grant| grant.data_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["A1", "A2"]
+        );
+
+        // A union would still return the A1 grant here.
+        assert!(contract
+            .find_grants_all_data_ids(grantee, vec!["A1".into(), "B1".into()])
+            .is_empty());
+    }
+
+    #[test]
+    fn settle_timelock_resets_a_passed_lock() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW + 10),
+            labeled("kyc"),
+        );
+        let grant_id = contract._find_grant_ids(Some(owner.clone()), None, None)[0].clone();
+
+        signed_by_at(&owner, NOW + 11);
+        let settled_id = contract.settle_timelock(grant_id.clone());
+
+        assert!(contract.get_grant(grant_id.clone()).is_none());
+        let settled = contract.get_grant(settled_id.clone()).unwrap();
+        assert_eq!(settled.locked_until, 0);
+        assert_eq!(settled.created_at, Some(NOW));
+        assert_eq!(
+            contract
+                .find_grants_by_label(Some(owner), "kyc".into())
+                .len(),
+            1
+        );
+        assert_eq!(logged_events()[0]["event"], "grant_timelock_settled");
+        assert_eq!(logged_events()[0]["data"]["old_locked_until"], NOW + 10);
+        assert_eq!(logged_events()[0]["data"]["old_grant_id"], grant_id);
+        assert_eq!(logged_events()[0]["data"]["new_grant_id"], settled_id);
+    }
+
+    #[test]
+    fn settle_timelock_keeps_the_attestation() {
+        let owner = public_key(1);
+        let grantee = public_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let grant_id = contract.insert_grant_with_options(
+            grantee.clone(),
+            "A1".into(),
+            Some(NOW + 10),
+            GrantOptions {
+                required_attestor: Some("attestor.near".parse().unwrap()),
+                ..GrantOptions::default()
+            },
+        );
+        called_by("attestor.near");
+        contract.attest(grant_id.clone());
+
+        signed_by_at(&owner, NOW + 11);
+        let settled_id = contract.settle_timelock(grant_id.clone());
+
+        assert!(!contract.attested_grant_ids.contains(&grant_id));
+        assert!(contract.attested_grant_ids.contains(&settled_id));
+        assert_eq!(contract.active_grants_for(grantee, "A1".into()).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant is timelocked")]
+    fn settle_timelock_rejects_active_locks() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW + 10));
+        let grant_id = contract._find_grant_ids(Some(owner), None, None)[0].clone();
+
+        contract.settle_timelock(grant_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Migrating, back at 12:00 UTC")]
+    fn maintenance_mode_rejects_writes_with_its_message() {
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_maintenance(true, Some("Migrating, back at 12:00 UTC".into()));
+
+        signed_by(&public_key(1));
+        contract.insert_grant(public_key(2), "A1".into(), None);
+    }
+
+    #[test]
+    fn views_work_in_maintenance_mode() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+
+        called_by_contract();
+        contract.set_maintenance(true, None);
+
+        assert_eq!(
+            contract
+                .find_grants(Some(owner.clone()), None, None, None, None)
+                .len(),
+            1
+        );
+        assert!(contract.is_granted(owner.clone(), public_key(2), "A1".into()));
+
+        contract.set_maintenance(false, None);
+        signed_by(&owner);
+        contract.insert_grant(public_key(2), "A2".into(), None);
+    }
+
+    #[test]
+    fn find_grants_orders_the_same_across_filter_combinations() {
+        let (alice, dave) = (public_key(1), public_key(4));
+        let bob = public_key(2);
+        let mut contract = FractalRegistry::default();
+        for (owner, data_id) in [
+            (&alice, "A2"),
+            (&dave, "A1"),
+            (&alice, "A1"),
+            (&alice, "A3"),
+        ] {
+            signed_by(owner);
+            contract.insert_grant(bob.clone(), data_id.into(), None);
+        }
+        signed_by(&alice);
+        contract.delete_grant(bob.clone(), "A2".into(), None);
+        contract.insert_grant(bob.clone(), "A2".into(), None);
+
+        let data_ids = |grants: Vec<Grant>| -> Vec<String> {
+            grants
+                .into_iter()
+                .filter(|grant| grant.owner == alice)
+                .map(|grant| grant.data_id)
+                .collect()
+        };
+        let expected = vec!["A1", "A3", "A2"];
+
+        assert_eq!(
+            data_ids(contract.find_grants(Some(alice.clone()), None, None, None, None)),
+            expected
+        );
+        assert_eq!(
+            data_ids(contract.find_grants(None, Some(bob.clone()), None, None, None)),
+            expected
+        );
+        assert_eq!(
+            data_ids(contract.find_grants(Some(alice.clone()), Some(bob), None, None, None)),
+            expected
+        );
+    }
+
+    #[test]
+    fn has_grants_batch_is_aligned_with_the_queries() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(3), "A2".into(), None);
+
+        assert_eq!(
+            contract.has_grants_batch(vec![
+                (public_key(2), "A1".into()),
+                (public_key(2), "A2".into()),
+                (public_key(3), "A2".into()),
+                (public_key(4), "A1".into()),
+            ]),
+            vec![true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn gateway_checks_ignore_expired_grants() {
+        let owner = public_key(1);
+        let grantee = public_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        contract.insert_grant_with_options(
+            grantee.clone(),
+            "A2".into(),
+            None,
+            GrantOptions {
+                expires_at: Some(NOW + 1),
+                ..GrantOptions::default()
+            },
+        );
+        let both = || vec!["A1".to_string(), "A2".to_string()];
+
+        signed_by_at(&owner, NOW + 1);
+        assert_eq!(
+            contract.has_grants_batch(vec![
+                (grantee.clone(), "A1".into()),
+                (grantee.clone(), "A2".into()),
+            ]),
+            vec![true, false]
+        );
+        assert!(contract.has_grants_on_all(grantee.clone(), vec!["A1".into()]));
+        assert!(!contract.has_grants_on_all(grantee.clone(), both()));
+        assert!(contract
+            .find_grants_all_data_ids(grantee.clone(), both())
+            .is_empty());
+        assert_eq!(
+            contract
+                .find_grants_all_data_ids(grantee, vec!["A1".into()])
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn over_shared_data_ids_counts_distinct_grantees() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        for grantee in 2..5 {
+            contract.insert_grant(public_key(grantee), "A1".into(), None);
+        }
+        contract.insert_grant(public_key(2), "A2".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), Some(5));
+        contract.insert_grant(public_key(3), "A2".into(), None);
+
+        assert_eq!(
+            contract.over_shared_data_ids(owner.clone(), 2, 0, 10),
+            vec![("A1".to_string(), 3)]
+        );
+        assert_eq!(
+            contract.over_shared_data_ids(owner.clone(), 1, 0, 10).len(),
+            2
+        );
+        assert_eq!(
+            contract.over_shared_data_ids(owner, 1, 4, 1),
+            vec![("A2".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn grants_expiring_in_a_ttl_stop_being_active_after_it() {
+        let grantee = public_key(2);
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_expiring_in(grantee.clone(), "A1".into(), 60);
+
+        signed_by_at(&public_key(1), NOW + 59_000_000_000);
+        assert_eq!(
+            contract
+                .active_grants_for(grantee.clone(), "A1".into())
+                .len(),
+            1
+        );
+
+        signed_by_at(&public_key(1), NOW + 60_000_000_000);
+        assert!(contract.active_grants_for(grantee, "A1".into()).is_empty());
+    }
+
+    #[test]
+    fn try_insert_grant_returns_typed_errors() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let try_insert = |contract: &mut FractalRegistry, data_id: &str, options: GrantOptions| {
+            contract.try_insert_grant(public_key(2), data_id.into(), None, Some(options))
+        };
+
+        assert_eq!(
+            try_insert(&mut contract, "", GrantOptions::default()),
+            Err(RegistryError::EmptyDataId)
+        );
+        assert_eq!(
+            try_insert(
+                &mut contract,
+                &"x".repeat(MAX_DATA_ID_LEN + 1),
+                GrantOptions::default()
+            ),
+            Err(RegistryError::DataIdTooLong)
+        );
+        assert_eq!(
+            try_insert(&mut contract, "A1", labeled("")),
+            Err(RegistryError::InvalidLabel)
+        );
+        assert_eq!(
+            try_insert(
+                &mut contract,
+                "A1",
+                GrantOptions {
+                    expires_at: Some(NOW),
+                    ..GrantOptions::default()
+                }
+            ),
+            Err(RegistryError::AlreadyExpired)
+        );
+
+        let grant_id = try_insert(&mut contract, "A1", GrantOptions::default()).unwrap();
+        assert_eq!(contract.try_get_grant(grant_id).unwrap().data_id, "A1");
+        assert_eq!(
+            try_insert(&mut contract, "A1", GrantOptions::default()),
+            Err(RegistryError::GrantAlreadyExists)
+        );
+
+        contract.freeze_own_grants();
+        assert_eq!(
+            try_insert(&mut contract, "A2", GrantOptions::default()),
+            Err(RegistryError::OwnerFrozen)
+        );
+        contract.unfreeze_own_grants();
+
+        called_by_contract();
+        contract.set_paused(true);
+        signed_by(&owner);
+        assert_eq!(
+            try_insert(&mut contract, "A2", GrantOptions::default()),
+            Err(RegistryError::Paused)
+        );
+
+        called_by_contract();
+        contract.set_maintenance(true, Some("Back soon".into()));
+        contract.set_reject_self_grant(true);
+        signed_by(&owner);
+        assert_eq!(
+            try_insert(&mut contract, "A2", GrantOptions::default()),
+            Err(RegistryError::Maintenance("Back soon".into()))
+        );
+        assert_eq!(
+            contract.try_insert_grant(owner.clone(), "A2".into(), None, None),
+            Err(RegistryError::SelfGrant)
+        );
+    }
+
+    #[test]
+    fn try_insert_grant_handles_idempotency_keys_like_insert_grant_with_options() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+
+        let first = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            with_idempotency_key("retry-1"),
+        );
+        assert_eq!(
+            contract.try_insert_grant(
+                public_key(2),
+                "A1".into(),
+                None,
+                Some(with_idempotency_key("retry-1"))
+            ),
+            Ok(first)
+        );
+        assert_eq!(
+            contract.try_insert_grant(
+                public_key(2),
+                "A2".into(),
+                None,
+                Some(with_idempotency_key(""))
+            ),
+            Err(RegistryError::InvalidIdempotencyKey)
+        );
+    }
+
+    #[test]
+    fn try_delete_grant_checked_returns_typed_errors() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let grant = contract.insert_and_get_grant(public_key(2), "A1".into(), Some(NOW + 10));
+        let grant_id = derive_grant_id(&grant);
+
+        assert_eq!(
+            contract.try_delete_grant_checked("missing".into(), fields_of(&grant)),
+            Err(RegistryError::GrantDoesNotExist)
+        );
+        assert_eq!(
+            contract.try_get_grant("missing".into()).err(),
+            Some(RegistryError::GrantDoesNotExist)
+        );
+        assert_eq!(
+            contract.try_delete_grant_checked(
+                grant_id.clone(),
+                GrantFields {
+                    data_id: "A2".into(),
+                    ..fields_of(&grant)
+                }
+            ),
+            Err(RegistryError::GrantMismatch)
+        );
+        assert_eq!(
+            contract.try_delete_grant_checked(grant_id.clone(), fields_of(&grant)),
+            Err(RegistryError::GrantIsTimelocked)
+        );
+
+        signed_by_at(&public_key(3), NOW + 11);
+        assert_eq!(
+            contract.try_delete_grant_checked(grant_id.clone(), fields_of(&grant)),
+            Err(RegistryError::NotGrantOwner)
+        );
+
+        signed_by_at(&owner, NOW + 11);
+        assert_eq!(
+            contract.try_delete_grant_checked(grant_id.clone(), fields_of(&grant)),
+            Ok(())
+        );
+        assert!(contract.get_grant(grant_id).is_none());
+    }
+
+    #[test]
+    fn try_delete_grant_returns_typed_errors() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW + 10));
+        contract.insert_grant_with_options(public_key(2), "A2".into(), None, immutable());
+        contract.insert_grant(public_key(2), "A3".into(), None);
+
+        assert_eq!(
+            contract.try_delete_grant(public_key(2), "A1".into(), None),
+            Err(RegistryError::GrantIsTimelocked)
+        );
+        assert_eq!(
+            contract.try_delete_grant(public_key(2), "A2".into(), None),
+            Err(RegistryError::GrantIsImmutable)
+        );
+        assert_eq!(
+            contract.try_delete_grant(public_key(2), "missing".into(), None),
+            Ok(0)
+        );
+
+        contract.freeze_own_grants();
+        assert_eq!(
+            contract.try_delete_grant(public_key(2), "A3".into(), None),
+            Err(RegistryError::OwnerFrozen)
+        );
+        contract.unfreeze_own_grants();
+
+        called_by_contract();
+        contract.set_paused(true);
+        signed_by(&owner);
+        assert_eq!(
+            contract.try_delete_grant(public_key(2), "A3".into(), None),
+            Err(RegistryError::Paused)
+        );
+
+        called_by_contract();
+        contract.set_paused(false);
+        signed_by(&owner);
+        assert_eq!(
+            contract.try_delete_grant(public_key(2), "A3".into(), None),
+            Ok(1)
+        );
+        assert_eq!(
+            contract
+                .find_grants(Some(owner), None, None, None, None)
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn registry_errors_display_as_the_panic_messages() {
+        let cases = [
+            (
+                RegistryError::UnsupportedKeyCurve,
+                ERR_UNSUPPORTED_KEY_CURVE,
+            ),
+            (RegistryError::EmptyDataId, ERR_EMPTY_DATA_ID),
+            (RegistryError::DataIdTooLong, ERR_DATA_ID_TOO_LONG),
+            (RegistryError::SelfGrant, ERR_SELF_GRANT),
+            (RegistryError::AlreadyExpired, ERR_ALREADY_EXPIRED),
+            (RegistryError::InvalidLabel, ERR_INVALID_LABEL),
+            (RegistryError::Maintenance("Back soon".into()), "Back soon"),
+            (RegistryError::Paused, ERR_PAUSED),
+            (RegistryError::OwnerFrozen, ERR_OWNER_FROZEN),
+            (RegistryError::GrantAlreadyExists, ERR_GRANT_EXISTS),
+            (RegistryError::GrantDoesNotExist, ERR_GRANT_DOES_NOT_EXIST),
+            (RegistryError::NotGrantOwner, ERR_NOT_GRANT_OWNER),
+            (RegistryError::GrantMismatch, ERR_GRANT_MISMATCH),
+            (RegistryError::GrantIsTimelocked, ERR_TIMELOCKED),
+            (RegistryError::GrantIsImmutable, ERR_IMMUTABLE),
+            (RegistryError::DurationTooLarge, ERR_DURATION_TOO_LARGE),
+            (RegistryError::DataIdClaimed, ERR_DATA_ID_CLAIMED),
+            (RegistryError::TooManyCoOwners, ERR_TOO_MANY_CO_OWNERS),
+            (
+                RegistryError::LockedUntilWrongUnit,
+                ERR_LOCKED_UNTIL_WRONG_UNIT,
+            ),
+            (
+                RegistryError::InvalidIdempotencyKey,
+                ERR_INVALID_IDEMPOTENCY_KEY,
+            ),
+            (
+                RegistryError::IdempotencyKeyReused,
+                ERR_IDEMPOTENCY_KEY_REUSED,
+            ),
+        ];
+
+        for (error, message) in cases {
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    // The ERR_ constants spell their limits out so they stay plain strings; this catches a limit changing
+    // without its message.
+    #[test]
+    fn error_messages_match_the_limits_they_mention() {
+        assert_eq!(
+            ERR_DATA_ID_TOO_LONG,
+            format!("data_id must be at most {} bytes long", MAX_DATA_ID_LEN)
+        );
+        assert_eq!(
+            ERR_INVALID_LABEL,
+            format!("label must be 1 to {} bytes long", MAX_LABEL_LEN)
+        );
+        assert_eq!(
+            ERR_INVALID_IDEMPOTENCY_KEY,
+            format!(
+                "idempotency_key must be 1 to {} bytes long",
+                MAX_IDEMPOTENCY_KEY_LEN
+            )
+        );
+        assert_eq!(
+            ERR_TOO_MANY_CO_OWNERS,
+            format!("a grant can have at most {} co_owners", MAX_CO_OWNERS)
+        );
+        assert_eq!(
+            ERR_BATCH_TOO_LARGE,
+            format!("Batch is larger than MAX_BATCH_SIZE ({})", MAX_BATCH_SIZE)
+        );
+        assert_eq!(
+            ERR_TOO_MANY_RESULTS,
+            format!(
+                "More than {} grants match, use find_grants_page or pass a limit",
+                MAX_RESULTS
+            )
+        );
+        assert_eq!(
+            ERR_N_TOO_LARGE,
+            format!("n must be at most {}", MAX_RESULTS)
+        );
+        assert_eq!(
+            ERR_LIMIT_TOO_LARGE,
+            format!("limit must be at most {}", MAX_RESULTS)
+        );
+    }
+
+    #[test]
+    fn owner_has_any_grant_tracks_the_owners_index() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        assert!(!contract.owner_has_any_grant(owner.clone()));
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        assert!(contract.owner_has_any_grant(owner.clone()));
+
+        contract.delete_grant(public_key(2), "A1".into(), None);
+        assert!(!contract.owner_has_any_grant(owner));
+    }
+
+    fn immutable() -> GrantOptions {
+        GrantOptions {
+            immutable: true,
+            ..GrantOptions::default()
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant is immutable")]
+    fn immutable_grants_cant_be_deleted_even_with_the_override() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(public_key(2), "A1".into(), None, immutable());
+        called_by_contract();
+        contract.set_timelock_override(true);
+
+        signed_by(&public_key(1));
+        contract.delete_grant(public_key(2), "A1".into(), None);
+    }
+
+    #[test]
+    fn bulk_deletes_skip_immutable_grants() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let grant_id =
+            contract.insert_grant_with_options(public_key(2), "A1".into(), None, immutable());
+        contract.insert_grant(public_key(3), "A1".into(), None);
+        called_by_contract();
+        contract.set_timelock_override(true);
+
+        signed_by(&owner);
+        assert_eq!(contract.delete_grants_for_data_id("A1".into()), 1);
+        assert_eq!(contract.revoke_all(public_key(2), "A1".into()), 0);
+        let grant = contract.get_grant(grant_id.clone()).unwrap();
+        assert_eq!(
+            contract.try_delete_grant_checked(grant_id, fields_of(&grant)),
+            Err(RegistryError::GrantIsImmutable)
+        );
+    }
+
+    #[test]
+    fn find_grants_filters_on_an_exact_locked_until() {
+        let grantee = public_key(2);
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        contract.insert_grant(grantee.clone(), "A2".into(), Some(NOW + 10));
+        contract.insert_grant(grantee.clone(), "A3".into(), Some(NOW + 10));
+        contract.insert_grant(grantee.clone(), "A4".into(), Some(NOW + 20));
+
+        let data_ids = |locked_until| -> Vec<String> {
+            contract
+                .find_grants(None, Some(grantee.clone()), None, None, Some(locked_until))
+                .into_iter()
+                .map(|grant| grant.data_id)
+                .collect()
+        };
+
+        assert_eq!(data_ids(NOW + 10), vec!["A2", "A3"]);
+        assert_eq!(data_ids(0), vec!["A1"]);
+        assert!(data_ids(NOW).is_empty());
+    }
+
+    #[test]
+    fn find_grants_caps_the_filtered_results() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        for i in 0..MAX_RESULTS {
+            // Resets the logs, which would otherwise go over the limit.
+            signed_by(&owner);
+            contract.insert_grant(public_key(2), format!("A{}", i), None);
+        }
+        contract.insert_grant(public_key(3), "A0".into(), Some(NOW + 10));
+
+        assert_eq!(
+            contract
+                .find_grants(Some(owner.clone()), None, None, Some(public_key(3)), None)
+                .len(),
+            MAX_RESULTS
+        );
+        assert_eq!(
+            contract
+                .find_grants(Some(owner.clone()), None, None, None, Some(0))
+                .len(),
+            MAX_RESULTS
+        );
+    }
+
+    #[test]
+    fn data_ids_missing_on_lists_what_the_target_lacks() {
+        let (source, target) = (public_key(1), public_key(3));
+        let mut contract = FractalRegistry::default();
+        signed_by(&source);
+        contract.insert_grant(public_key(2), "X".into(), None);
+        contract.insert_grant(public_key(2), "Y".into(), None);
+        contract.insert_grant(public_key(4), "Y".into(), None);
+        signed_by(&target);
+        contract.insert_grant(public_key(4), "X".into(), None);
+
+        assert_eq!(
+            contract.data_ids_missing_on(source.clone(), target.clone(), 0, 10),
+            vec!["Y"]
+        );
+        assert!(contract
+            .data_ids_missing_on(source.clone(), target.clone(), 0, 1)
+            .is_empty());
+        assert!(contract
+            .data_ids_missing_on(target, source, 0, 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn views_skip_dangling_ids() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions {
+                label: Some("kyc".into()),
+                ..GrantOptions::default()
+            },
+        );
+        let orphan = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW - 1),
+            GrantOptions {
+                label: Some("kyc".into()),
+                ..GrantOptions::default()
+            },
+        );
+        contract.grants_by_id.remove(&orphan);
+
+        assert_eq!(
+            contract
+                .find_grants(Some(owner.clone()), None, None, None, None)
+                .len(),
+            1
+        );
+        assert_eq!(contract.grants_for(public_key(2), "A1".into()).len(), 1);
+        assert_eq!(
+            contract.active_grants_for(public_key(2), "A1".into()).len(),
+            1
+        );
+        assert_eq!(contract.recent_grants_for_owner(owner.clone(), 2).len(), 1);
+        assert_eq!(contract.find_grants_by_label(None, "kyc".into()).len(), 1);
+        assert!(contract.find_stale_grants(owner.clone(), 10).is_empty());
+        assert_eq!(contract.purge_expired_grants(10), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn sweep_orphans_is_rejected_while_paused() {
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_paused(true);
+
+        contract.sweep_orphans(public_key(1), 10);
+    }
+
+    #[test]
+    fn sweep_orphans_drops_dangling_ids_from_the_owners_index() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let kept = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions::default(),
+        );
+        for data_id in ["A2", "A3", "A4"] {
+            let orphan = contract.insert_grant_with_options(
+                public_key(2),
+                data_id.into(),
+                None,
+                GrantOptions::default(),
+            );
+            contract.grants_by_id.remove(&orphan);
+        }
+
+        called_by_contract();
+        assert_eq!(contract.sweep_orphans(owner.clone(), 2), 2);
+        assert_eq!(logged_events()[0]["event"], "orphans_swept");
+        assert_eq!(logged_events()[0]["data"]["count"], 2);
+        assert_eq!(contract.sweep_orphans(owner.clone(), 10), 1);
+        assert_eq!(contract.sweep_orphans(owner.clone(), 10), 0);
+
+        assert_eq!(
+            *contract.grant_ids_by_owner.get(&owner).unwrap(),
+            vec![kept]
+        );
+    }
+
+    #[test]
+    fn grant_id_hashes_are_stable_and_distinct() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        let grant = contract.insert_and_get_grant(public_key(2), "A1".into(), Some(1337));
+
+        let keccak = derive_grant_id_with(&grant, GrantIdHash::Keccak256);
+        let sha256 = derive_grant_id_with(&grant, GrantIdHash::Sha256);
+
+        assert_eq!(keccak, derive_grant_id_with(&grant, GrantIdHash::Keccak256));
+        assert_eq!(sha256, derive_grant_id_with(&grant, GrantIdHash::Sha256));
+        assert_eq!(sha256.len(), 64);
+        assert_ne!(keccak, sha256);
+        assert_eq!(
+            derive_grant_id(&grant),
+            derive_grant_id_with(&grant, GRANT_ID_HASH)[..2 * GRANT_ID_BYTES]
+        );
+    }
+
+    #[test]
+    fn all_data_ids_tracks_data_ids_until_their_last_grant_goes() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(3), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), None);
+        contract.insert_grant(public_key(2), "A3".into(), None);
+        assert_eq!(contract.all_data_ids(0, 10), vec!["A1", "A2", "A3"]);
+        assert_eq!(contract.all_data_ids(1, 1), vec!["A2"]);
+
+        contract.delete_grant(public_key(2), "A1".into(), None);
+        assert_eq!(contract.all_data_ids(0, 10), vec!["A1", "A2", "A3"]);
+
+        contract.delete_grant(public_key(3), "A1".into(), None);
+        assert_eq!(contract.all_data_ids(0, 10), vec!["A3", "A2"]);
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.delete_grant(public_key(2), "A3".into(), None);
+        assert_eq!(contract.all_data_ids(0, 10), vec!["A1", "A2"]);
+    }
+
+    #[test]
+    fn find_grants_advanced_combines_filters() {
+        let owner = public_key(1);
+        let grantee = public_key(2);
+        let mut contract = FractalRegistry::default();
+        for (at, data_id, locked_until) in [
+            (NOW, "kyc-1", None),
+            (NOW + 10, "kyc-2", Some(NOW + 100)),
+            (NOW + 20, "aml-1", None),
+            (NOW + 30, "kyc-3", Some(NOW + 200)),
+        ] {
+            signed_by_at(&owner, at);
+            contract.insert_grant(grantee.clone(), data_id.into(), locked_until);
+        }
+        signed_by_at(&public_key(3), NOW + 10);
+        contract.insert_grant(grantee.clone(), "kyc-9".into(), None);
+
+        let data_ids = |query: GrantQuery, from_index, limit| -> Vec<String> {
+            contract
+                .find_grants_advanced(query, from_index, limit)
+                .into_iter()
+                .map(|grant| grant.data_id)
+                .collect()
+        };
+
+        let kyc_for_grantee = GrantQuery {
+            grantee: Some(grantee.clone()),
+            data_id_prefix: Some("kyc-".into()),
+            ..GrantQuery::default()
+        };
+        assert_eq!(
+            data_ids(kyc_for_grantee.clone(), None, None),
+            vec!["kyc-1", "kyc-2", "kyc-3", "kyc-9"]
+        );
+        assert_eq!(
+            data_ids(
+                GrantQuery {
+                    owner: Some(owner.clone()),
+                    created_from: Some(NOW + 5),
+                    created_to: Some(NOW + 30),
+                    ..kyc_for_grantee.clone()
+                },
+                None,
+                None
+            ),
+            vec!["kyc-2", "kyc-3"]
+        );
+        assert_eq!(
+            data_ids(
+                GrantQuery {
+                    locked_until_min: Some(NOW + 150),
+                    ..kyc_for_grantee.clone()
+                },
+                None,
+                None
+            ),
+            vec!["kyc-3"]
+        );
+        assert_eq!(
+            data_ids(kyc_for_grantee.clone(), Some(1), Some(2)),
+            vec!["kyc-2", "kyc-3"]
+        );
+        assert_eq!(
+            data_ids(
+                GrantQuery {
+                    data_id: Some("aml-1".into()),
+                    ..GrantQuery::default()
+                },
+                None,
+                None
+            ),
+            vec!["aml-1"]
+        );
+        assert!(data_ids(
+            GrantQuery {
+                created_to: Some(NOW - 1),
+                ..kyc_for_grantee
+            },
+            None,
+            None
+        )
+        .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Required argument: `owner`, `grantee` and/or `data_id`")]
+    fn find_grants_advanced_requires_an_indexed_filter() {
+        FractalRegistry::default().find_grants_advanced(
+            GrantQuery {
+                data_id_prefix: Some("kyc-".into()),
+                ..GrantQuery::default()
+            },
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn reassign_grant_moves_a_grant_to_a_new_grantee() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let grant_id =
+            contract.insert_grant_with_options(public_key(2), "A1".into(), None, labeled("kyc"));
+
+        signed_by(&owner);
+        let new_grant_id = contract.reassign_grant(grant_id.clone(), public_key(3));
+
+        assert!(contract.get_grant(grant_id.clone()).is_none());
+        assert!(contract.grants_for(public_key(2), "A1".into()).is_empty());
+        let reassigned = contract.get_grant(new_grant_id.clone()).unwrap();
+        assert_eq!(reassigned.grantee, public_key(3));
+        assert_eq!(reassigned.label, Some("kyc".into()));
+        let event = &logged_events()[0];
+        assert_eq!(event["event"], "grant_reassigned");
+        assert_eq!(event["data"]["old_grant_id"], grant_id);
+        assert_eq!(event["data"]["new_grant_id"], new_grant_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the grant's owner can reassign it")]
+    fn only_the_owner_can_reassign_a_grant() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        let grant_id = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions::default(),
+        );
+
+        signed_by(&public_key(2));
+        contract.reassign_grant(grant_id, public_key(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant doesn't exist")]
+    fn reassign_grant_rejects_unknown_ids() {
+        signed_by(&public_key(1));
+        FractalRegistry::default().reassign_grant("missing".into(), public_key(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Grantee can't be the owner")]
+    fn reassign_grant_rejects_self_grants_in_strict_mode() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_reject_self_grant(true);
+
+        signed_by(&owner);
+        let grant_id = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions::default(),
+        );
+        contract.reassign_grant(grant_id, owner);
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant is immutable")]
+    fn reassign_grant_rejects_immutable_grants() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        let grant_id =
+            contract.insert_grant_with_options(public_key(2), "A1".into(), None, immutable());
+
+        contract.reassign_grant(grant_id, public_key(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant is timelocked")]
+    fn reassign_grant_respects_timelocks() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        let grant_id = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW + 10),
+            GrantOptions::default(),
+        );
+
+        contract.reassign_grant(grant_id, public_key(3));
+    }
+
+    #[test]
+    fn grants_for_from_owner_only_returns_that_owners_grants() {
+        let (alice, dave) = (public_key(1), public_key(4));
+        let grantee = public_key(2);
+        let mut contract = FractalRegistry::default();
+        signed_by(&alice);
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        signed_by(&dave);
+        contract.insert_grant(grantee.clone(), "A1".into(), Some(5));
+
+        let grants = contract.grants_for_from_owner(dave.clone(), grantee.clone(), "A1".into());
+
+        assert_eq!(contract.grants_for(grantee.clone(), "A1".into()).len(), 2);
+        assert_eq!(grants.len(), 1);
+        assert_eq!(grants[0].owner, dave);
+        assert!(contract
+            .grants_for_from_owner(alice, grantee, "A2".into())
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "duration too large")]
+    fn ttls_that_overflow_are_rejected() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_expiring_in(public_key(2), "A1".into(), u64::MAX / 1_000_000_000);
+    }
+
+    #[test]
+    fn default_timelocks_that_overflow_are_rejected() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.set_default_timelock(Some(u64::MAX - NOW + 1));
+
+        assert_eq!(
+            contract.try_insert_grant(public_key(2), "A1".into(), None, None),
+            Err(RegistryError::DurationTooLarge)
+        );
+
+        contract.set_default_timelock(Some(u64::MAX - NOW));
+        let grant = contract.insert_and_get_grant(public_key(2), "A1".into(), None);
+        assert_eq!(grant.locked_until, u64::MAX);
+    }
+
+    fn with_data_id_claims() -> FractalRegistry {
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_data_id_claims(true);
+        contract
+    }
+
+    #[test]
+    fn the_first_owner_to_grant_on_a_data_id_claims_it() {
+        let owner = public_key(1);
+        let mut contract = with_data_id_claims();
+        signed_by(&owner);
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+        contract.insert_grant(public_key(3), "user123/passport".into(), None);
+
+        assert_eq!(
+            contract.data_id_claimant("user123/passport".into()),
+            Some(owner)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "data_id owned by another account")]
+    fn other_owners_cant_grant_on_a_claimed_data_id() {
+        let mut contract = with_data_id_claims();
+        signed_by(&public_key(1));
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+
+        signed_by(&public_key(4));
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "data_id owned by another account")]
+    fn data_ids_granted_on_before_claims_cant_be_claimed_by_someone_else() {
+        let mut contract = FractalRegistry::default();
+        signed_by(&public_key(1));
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+
+        called_by_contract();
+        contract.set_data_id_claims(true);
+        signed_by(&public_key(4));
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+    }
+
+    #[test]
+    fn data_ids_granted_on_before_claims_can_be_claimed_by_their_owner() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        signed_by(&owner);
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+
+        called_by_contract();
+        contract.set_data_id_claims(true);
+        signed_by(&owner);
+        contract.insert_grant(public_key(3), "user123/passport".into(), None);
+
+        assert_eq!(
+            contract.data_id_claimant("user123/passport".into()),
+            Some(owner)
+        );
+    }
+
+    #[test]
+    fn the_contract_account_can_clear_claims() {
+        let mut contract = with_data_id_claims();
+        signed_by(&public_key(1));
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+        contract.delete_grant(public_key(2), "user123/passport".into(), None);
+
+        called_by_contract();
+        contract.clear_data_id_claim("user123/passport".into());
+        signed_by(&public_key(4));
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+
+        assert_eq!(
+            contract.data_id_claimant("user123/passport".into()),
+            Some(public_key(4))
+        );
+    }
+
+    #[test]
+    fn debug_index_views_show_the_raw_indexes() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let first = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions::default(),
+        );
+        let second = contract.insert_grant_with_options(
+            public_key(3),
+            "A1".into(),
+            None,
+            GrantOptions::default(),
+        );
+
+        called_by_contract();
+        assert_eq!(
+            contract.debug_index_for_owner(owner.clone()),
+            vec![first.clone(), second.clone()]
+        );
+        assert_eq!(
+            contract.debug_index_for_grantee(public_key(2)),
+            vec![first.clone()]
+        );
+        assert_eq!(
+            contract.debug_index_for_data_id("A1".into()),
+            vec![first, second.clone()]
+        );
+
+        signed_by(&owner);
+        contract.delete_grant(public_key(2), "A1".into(), None);
+
+        called_by_contract();
+        assert_eq!(contract.debug_index_for_owner(owner), vec![second.clone()]);
+        assert!(contract.debug_index_for_grantee(public_key(2)).is_empty());
+        assert_eq!(contract.debug_index_for_data_id("A1".into()), vec![second]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract account can do this")]
+    fn debug_index_views_are_for_the_contract_account_only() {
+        let contract = FractalRegistry::default();
+        signed_by(&public_key(1));
+        contract.debug_index_for_owner(public_key(1));
+    }
+
+    #[test]
+    fn prune_grants_for_owner_removes_them_from_every_index() {
+        let (owner, other) = (public_key(1), public_key(4));
+        let mut contract = with_data_id_claims();
+        signed_by(&other);
+        let kept = contract.insert_grant_with_options(
+            public_key(2),
+            "B1".into(),
+            None,
+            GrantOptions::default(),
+        );
+        signed_by(&owner);
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW + 1_000));
+        contract.insert_grant(public_key(3), "A2".into(), None);
+
+        called_by_contract();
+        assert_eq!(
+            contract.prune_grants_for_owner(owner.clone(), 1),
+            PruneReport {
+                pruned: 1,
+                immutable: 0
+            }
+        );
+        assert_eq!(logged_events()[0]["event"], "grants_pruned");
+        assert_eq!(logged_events()[0]["data"]["count"], 1);
+        assert_eq!(contract.prune_grants_for_owner(owner.clone(), 10).pruned, 1);
+        assert_eq!(contract.prune_grants_for_owner(owner.clone(), 10).pruned, 0);
+
+        assert!(contract.debug_index_for_owner(owner).is_empty());
+        assert_eq!(contract.debug_index_for_grantee(public_key(2)), vec![kept]);
+        assert!(contract.debug_index_for_grantee(public_key(3)).is_empty());
+        assert!(contract.debug_index_for_data_id("A1".into()).is_empty());
+        assert_eq!(contract.all_data_ids(0, 10), vec!["B1"]);
+        assert_eq!(contract.data_id_claimant("A1".into()), None);
+    }
+
+    #[test]
+    fn prune_grants_for_owner_leaves_immutable_grants_in_place() {
+        let owner = public_key(1);
+        let mut contract = with_data_id_claims();
+        signed_by(&owner);
+        let kept =
+            contract.insert_grant_with_options(public_key(2), "A1".into(), None, immutable());
+        contract.insert_grant(public_key(3), "A1".into(), None);
+        contract.insert_grant(public_key(3), "A2".into(), None);
+
+        called_by_contract();
+        assert_eq!(
+            contract.prune_grants_for_owner(owner.clone(), 1),
+            PruneReport {
+                pruned: 1,
+                immutable: 1
+            }
+        );
+        assert_eq!(
+            contract.prune_grants_for_owner(owner.clone(), 10),
+            PruneReport {
+                pruned: 1,
+                immutable: 1
+            }
+        );
+
+        assert_eq!(contract.debug_index_for_owner(owner.clone()), vec![kept]);
+        assert_eq!(contract.data_id_claimant("A1".into()), Some(owner));
+        assert_eq!(contract.data_id_claimant("A2".into()), None);
+    }
+
+    #[test]
+    fn find_grants_with_embedded_id_puts_the_grant_id_in_the_json() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+
+        let grants = contract.find_grants_with_embedded_id(Some(public_key(1)), None, None);
+        let json = serde_json::to_value(&grants).unwrap();
+
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["grant_id"], derive_grant_id(&grants[0].grant));
+        assert_eq!(json[0]["data_id"], "A1");
+        assert_eq!(
+            json[0]["owner"],
+            serde_json::to_value(public_key(1)).unwrap()
+        );
+    }
+
+    #[test]
+    fn delete_grant_returns_how_many_grants_it_deleted() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), Some(1));
+        contract.insert_grant(public_key(2), "A1".into(), Some(2));
+
+        signed_by(&public_key(1));
+        assert_eq!(contract.delete_grant(public_key(2), "A1".into(), None), 2);
+        assert_eq!(logged_events().len(), 2);
+    }
+
+    #[test]
+    fn delete_grant_without_matches_returns_0_and_emits_nothing() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), Some(1));
+        contract.delete_grant(public_key(2), "A1".into(), None);
+
+        signed_by(&public_key(1));
+        assert_eq!(contract.delete_grant(public_key(2), "A1".into(), None), 0);
+        assert_eq!(contract.delete_grant(public_key(3), "A1".into(), None), 0);
+        assert!(logged_events().is_empty());
+    }
+
+    #[test]
+    fn grantees_expiring_within_only_lists_grants_expiring_in_the_window() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        for (grantee, expires_at) in [(2, NOW + 10), (3, NOW + 100), (4, NOW + 1_000)] {
+            contract.insert_grant_with_options(
+                public_key(grantee),
+                "A1".into(),
+                None,
+                GrantOptions {
+                    expires_at: Some(expires_at),
+                    ..GrantOptions::default()
+                },
+            );
+        }
+        contract.insert_grant(public_key(5), "A1".into(), None);
+
+        signed_by_at(&owner, NOW + 10);
+        assert_eq!(
+            contract.grantees_expiring_within(owner.clone(), 100, 0, 10),
+            vec![(public_key(3), "A1".to_string(), NOW + 100)]
+        );
+        assert!(contract
+            .grantees_expiring_within(owner, 100, 2, 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn distinct_grantees_ignores_deletes_of_grantees_from_before_migrate() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut legacy = FractalRegistry::default();
+        legacy.insert_grant(public_key(2), "A1".into(), None);
+        // The legacy contract never wrote this.
+        legacy.counted_grantees.remove(&public_key(2));
+        env::state_write(&LegacyStateFixture {
+            grants_by_id: legacy.grants_by_id,
+            grant_ids_by_owner: legacy.grant_ids_by_owner,
+            grant_ids_by_grantee: legacy.grant_ids_by_grantee,
+            grant_ids_by_data_id: legacy.grant_ids_by_data_id,
+        });
+        let mut migrated = FractalRegistry::migrate();
+        migrated.insert_grant(public_key(3), "A1".into(), None);
+        assert_eq!(migrated.distinct_grantees(), 1);
+
+        migrated.delete_grant(public_key(2), "A1".into(), None);
+        assert_eq!(migrated.distinct_grantees(), 1);
+
+        migrated.insert_grant(public_key(2), "A2".into(), None);
+        assert_eq!(migrated.distinct_grantees(), 2);
+        migrated.delete_grant(public_key(2), "A2".into(), None);
+        assert_eq!(migrated.distinct_grantees(), 1);
+    }
+
+    #[test]
+    fn distinct_grantees_counts_grantees_with_at_least_one_grant() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), None);
+        assert_eq!(contract.distinct_grantees(), 1);
+
+        contract.insert_grant(public_key(3), "A1".into(), None);
+        assert_eq!(contract.distinct_grantees(), 2);
+
+        contract.delete_grant(public_key(2), "A1".into(), None);
+        assert_eq!(contract.distinct_grantees(), 2);
+        contract.delete_grant(public_key(2), "A2".into(), None);
+        assert_eq!(contract.distinct_grantees(), 1);
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        assert_eq!(contract.distinct_grantees(), 2);
+    }
+
+    #[test]
+    fn delete_grant_emits_one_event_per_deleted_grant() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let first = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW - 2),
+            GrantOptions::default(),
+        );
+        let second = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW - 1),
+            GrantOptions::default(),
+        );
+
+        signed_by(&owner);
+        contract.delete_grant(public_key(2), "A1".into(), None);
+
+        let events = logged_events();
+        assert_eq!(events.len(), 2);
+        for (event, (grant_id, locked_until)) in
+            events.iter().zip([(first, NOW - 2), (second, NOW - 1)])
+        {
+            assert_eq!(event["event"], "grant_deleted");
+            assert_eq!(event["data"]["grant_id"], grant_id);
+            assert_eq!(event["data"]["locked_until"], locked_until);
+        }
+    }
+
+    #[test]
+    fn rebuild_owner_index_restores_dropped_ids() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        for (data_id, at) in [("A2", NOW), ("A1", NOW + 1), ("A3", NOW + 2)] {
+            signed_by_at(&owner, at);
+            contract.insert_grant(public_key(2), data_id.into(), None);
+        }
+        signed_by(&public_key(4));
+        contract.insert_grant(public_key(2), "A1".into(), None);
+
+        let mut index = contract.grant_ids_by_owner.get(&owner).unwrap().clone();
+        let dropped = index.remove(1);
+        *contract.grant_ids_by_owner.get_mut(&owner).unwrap() = index.clone();
+
+        called_by_contract();
+        assert_eq!(
+            contract.rebuild_owner_index(owner.clone(), 0, 2),
+            IndexRebuild {
+                added: 1,
+                next_index: Some(2)
+            }
+        );
+        assert_eq!(logged_events()[0]["event"], "owner_index_rebuilt");
+        assert_eq!(logged_events()[0]["data"]["count"], 3);
+        assert_eq!(
+            contract.rebuild_owner_index(owner.clone(), 2, 2),
+            IndexRebuild {
+                added: 0,
+                next_index: None
+            }
+        );
+
+        index.push(dropped);
+        assert_eq!(*contract.grant_ids_by_owner.get(&owner).unwrap(), index);
+    }
+
+    #[test]
+    fn rebuild_owner_index_keeps_ids_it_cant_reach_through_all_data_ids() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), None);
+        let index = contract.grant_ids_by_owner.get(&owner).unwrap().clone();
+        // As on a contract upgraded without `backfill_data_ids`.
+        contract.data_ids.clear();
+        contract.data_id_positions.remove(&"A1".to_string());
+        contract.data_id_positions.remove(&"A2".to_string());
+
+        called_by_contract();
+        assert_eq!(contract.rebuild_owner_index(owner.clone(), 0, 10).added, 0);
+        assert_eq!(*contract.grant_ids_by_owner.get(&owner).unwrap(), index);
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch is larger than MAX_BATCH_SIZE (32)")]
+    fn rebuild_owner_index_rejects_a_limit_that_truncates_into_range() {
+        let mut contract = FractalRegistry::default();
+
+        called_by_contract();
+        contract.rebuild_owner_index(public_key(1), 0, (1 << 32) + 1);
+    }
+
+    #[test]
+    fn find_grant_ids_matches_find_grants() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.insert_grant(public_key(3), "A1".into(), None);
+        contract.insert_grant(public_key(2), "A2".into(), None);
+
+        for (grantee, data_id) in [
+            (None, None),
+            (Some(public_key(2)), None),
+            (None, Some("A1".to_string())),
+            (Some(public_key(3)), Some("A2".to_string())),
+        ] {
+            let grant_ids =
+                contract.find_grant_ids(Some(owner.clone()), grantee.clone(), data_id.clone());
+            let grants = contract.find_grants(Some(owner.clone()), grantee, data_id, None, None);
+
+            assert_eq!(
+                grant_ids,
+                grants.iter().map(derive_grant_id).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    // PublicKey holds the decoded bytes, so every accepted spelling of a key is already the same value.
+    #[test]
+    fn grantee_key_spellings_collide_to_one_grant() {
+        let grantee = public_key(2);
+        let base58 = String::from(&grantee);
+        let spellings: Vec<PublicKey> = [
+            base58.clone(),
+            base58.replacen("ed25519:", "ED25519:", 1),
+            base58.trim_start_matches("ed25519:").to_string(),
+        ]
+        .iter()
+        .map(|spelling| serde_json::from_value(Value::String(spelling.clone())).unwrap())
+        .collect();
+
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(spellings[0].clone(), "A1".into(), None);
+        for spelling in &spellings[1..] {
+            assert_eq!(
+                contract.try_insert_grant(spelling.clone(), "A1".into(), None, None),
+                Err(RegistryError::GrantAlreadyExists)
+            );
+        }
+
+        assert_eq!(
+            contract.grant_ids_by_grantee.get(&grantee).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn time_until_revocable_counts_down_to_the_end_of_the_timelock() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let locked = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW + 1_000),
+            GrantOptions::default(),
+        );
+        let unlocked = contract.insert_grant_with_options(
+            public_key(2),
+            "A2".into(),
+            None,
+            GrantOptions::default(),
+        );
+
+        assert_eq!(contract.time_until_revocable(locked.clone()), Some(1_001));
+        assert!(contract.time_until_revocable(unlocked).unwrap() <= 0);
+        assert_eq!(contract.time_until_revocable("nope".into()), None);
+
+        signed_by_at(&owner, NOW + 1_001);
+        assert_eq!(contract.time_until_revocable(locked.clone()), Some(0));
+        contract.delete_grant(public_key(2), "A1".into(), None);
+        assert_eq!(contract.time_until_revocable(locked), None);
+    }
+
+    fn co_owned_by(co_owner: &PublicKey) -> GrantOptions {
+        GrantOptions {
+            co_owners: vec![co_owner.clone()],
+            ..GrantOptions::default()
+        }
+    }
+
+    #[test]
+    fn co_owners_can_delete_grants() {
+        let (owner, co_owner) = (public_key(1), public_key(4));
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            co_owned_by(&co_owner),
+        );
+        let by_id = contract.insert_grant_with_options(
+            public_key(3),
+            "A1".into(),
+            None,
+            co_owned_by(&co_owner),
+        );
+        contract.insert_grant(public_key(2), "A2".into(), None);
+
+        signed_by(&co_owner);
+        assert_eq!(
+            contract.delete_grant_as_co_owner(owner.clone(), public_key(2), "A1".into(), None),
+            1
+        );
+        assert_eq!(
+            contract.delete_grant_as_co_owner(owner.clone(), public_key(2), "A2".into(), None),
+            0,
+            "not a co-owner of that one"
+        );
+        let expected = fields_of(&contract.get_grant(by_id.clone()).unwrap());
+        contract.delete_grant_checked(by_id, expected);
+
+        assert_eq!(contract.find_grant_ids(Some(owner), None, None).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant is timelocked")]
+    fn co_owners_cant_delete_timelocked_grants() {
+        let (owner, co_owner) = (public_key(1), public_key(4));
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW + 1_000),
+            co_owned_by(&co_owner),
+        );
+
+        signed_by(&co_owner);
+        contract.delete_grant_as_co_owner(owner, public_key(2), "A1".into(), None);
+    }
+
+    #[test]
+    fn co_owners_arent_part_of_the_grant_id() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        let co_owned = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            co_owned_by(&public_key(4)),
+        );
+        contract.delete_grant(public_key(2), "A1".into(), None);
+        let plain = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions::default(),
+        );
+
+        assert_eq!(co_owned, plain);
+    }
+
+    #[test]
+    #[should_panic(expected = "a grant can have at most 8 co_owners")]
+    fn grants_have_a_bounded_number_of_co_owners() {
+        signed_by(&public_key(1));
+        FractalRegistry::default().insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions {
+                co_owners: (10..19).map(public_key).collect(),
+                ..GrantOptions::default()
+            },
+        );
+    }
+
+    #[test]
+    fn get_config_reflects_the_limits_and_settings() {
+        let mut contract = FractalRegistry::default();
+        let config = serde_json::to_value(contract.get_config()).unwrap();
+        assert_eq!(config["max_batch_size"], 32);
+        assert_eq!(config["max_results"], 100);
+        assert_eq!(config["max_data_id_len"], 256);
+        assert_eq!(config["reject_self_grant"], false);
+        assert_eq!(config["audit_log_enabled"], false);
+        assert_eq!(config["paused"], false);
+        assert_eq!(config["maintenance_message"], Value::Null);
+
+        called_by_contract();
+        contract.set_reject_self_grant(true);
+        contract.set_timelock_override(true);
+        contract.set_data_id_claims(true);
+        contract.set_audit_log(true);
+        contract.set_paused(true);
+        contract.set_maintenance(true, Some("Back soon".into()));
+
+        let config = serde_json::to_value(contract.get_config()).unwrap();
+        assert_eq!(config["reject_self_grant"], true);
+        assert_eq!(config["timelock_override_enabled"], true);
+        assert_eq!(config["data_id_claims_enabled"], true);
+        assert_eq!(config["audit_log_enabled"], true);
+        assert_eq!(config["paused"], true);
+        assert_eq!(config["maintenance_message"], "Back soon");
+    }
+
+    #[test]
+    fn try_find_grants_reports_bad_queries_instead_of_panicking() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(3), "A1".into(), None);
+
+        assert_eq!(
+            serde_json::to_value(contract.try_find_grants(
+                None,
+                None,
+                Some("A1".into()),
+                None,
+                None
+            ))
+            .unwrap(),
+            "missing_filter"
+        );
+
+        let found = serde_json::to_value(contract.try_find_grants(
+            Some(owner.clone()),
+            None,
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(found["ok"].as_array().unwrap().len(), 1);
+        assert_eq!(found["ok"][0]["data_id"], "A1");
+
+        insert_many(&mut contract, &owner, MAX_RESULTS);
+        assert_eq!(
+            serde_json::to_value(contract.try_find_grants(
+                Some(owner.clone()),
+                None,
+                None,
+                None,
+                None
+            ))
+            .unwrap(),
+            "too_many_results"
+        );
+        let filtered = serde_json::to_value(contract.try_find_grants(
+            Some(owner),
+            None,
+            None,
+            Some(public_key(3)),
+            None,
+        ))
+        .unwrap();
+        assert_eq!(filtered["ok"].as_array().unwrap().len(), MAX_RESULTS);
+    }
+
+    #[test]
+    fn exported_grants_import_under_another_owner() {
+        let (old_owner, new_owner) = (public_key(1), public_key(4));
+        signed_by(&old_owner);
+        let mut contract = FractalRegistry::default();
+        let old_ids = vec![
+            contract.insert_grant_with_options(
+                public_key(2),
+                "A1".into(),
+                Some(NOW + 1_000),
+                labeled("kyc"),
+            ),
+            contract.insert_grant_with_options(public_key(3), "A2".into(), None, immutable()),
+        ];
+        contract.insert_grant_with_options(
+            public_key(2),
+            "A3".into(),
+            None,
+            GrantOptions {
+                expires_at: Some(NOW + 1),
+                ..GrantOptions::default()
+            },
+        );
+
+        signed_by_at(&old_owner, NOW + 1);
+        let exported = contract.export_owner_grants(old_owner.clone(), None, None);
+        assert_eq!(exported.len(), 2, "the expired grant isn't exported");
+
+        signed_by_at(&new_owner, NOW + 1);
+        let new_ids = contract.import_owner_grants(exported.clone(), None);
+
+        assert!(new_ids.iter().all(|id| !old_ids.contains(id)));
+        assert_eq!(
+            contract.export_owner_grants(new_owner, None, None),
+            exported
+        );
+    }
+
+    #[test]
+    fn events_say_which_block_and_account_they_came_from() {
+        let mut contract = FractalRegistry::default();
+        testing_env!(VMContextBuilder::new()
+            .signer_account_pk(public_key(1))
+            .predecessor_account_id("relayer.near".parse().unwrap())
+            .block_height(42)
+            .build());
+        contract.insert_grant(public_key(2), "A1".into(), None);
+
+        let data = &logged_events()[0]["data"];
+        assert_eq!(data["block_height"], 42);
+        assert_eq!(data["predecessor"], "relayer.near");
+        assert_eq!(data["data_id"], "A1");
+    }
+
+    #[test]
+    fn active_data_ids_for_grantee_only_keeps_actively_granted_data_ids() {
+        let owner = public_key(1);
+        let grantee = public_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), None);
+        contract.insert_grant(grantee.clone(), "A3".into(), None);
+        contract.insert_grant_with_options(
+            grantee.clone(),
+            "A2".into(),
+            None,
+            GrantOptions {
+                expires_at: Some(NOW + 1),
+                ..GrantOptions::default()
+            },
+        );
+        contract.insert_grant(public_key(3), "A4".into(), None);
+
+        signed_by_at(&owner, NOW + 1);
+        assert_eq!(
+            contract.active_data_ids_for_grantee(
+                grantee,
+                vec!["A3".into(), "A2".into(), "A1".into(), "A4".into()]
+            ),
+            vec!["A3", "A1"]
+        );
+    }
+
+    #[test]
+    fn the_audit_log_records_inserts_and_deletes_in_order() {
+        let owner = public_key(1);
+        let mut contract = FractalRegistry::default();
+        signed_by(&owner);
+        contract.insert_grant(public_key(2), "unaudited".into(), None);
+        flush(&mut contract);
+
+        called_by_contract();
+        contract.set_audit_log(true);
+        testing_env!(VMContextBuilder::new()
+            .signer_account_pk(owner.clone())
+            .predecessor_account_id("relayer.near".parse().unwrap())
+            .block_height(7)
+            .block_timestamp(NOW)
+            .build());
+        let worst_case =
+            contract.estimate_insert_cost(owner.clone(), public_key(2), "A1".into(), None);
+        let estimate = contract.estimate_insert_cost(
+            owner.clone(),
+            public_key(2),
+            "A1".into(),
+            Some("relayer.near".parse().unwrap()),
+        );
+        assert_eq!(
+            worst_case.storage_bytes - estimate.storage_bytes,
+            (MAX_ACCOUNT_ID_LEN - "relayer.near".len()) as u64
+        );
+        let before = contract.storage_usage();
+        let grant_id = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions::default(),
+        );
+        flush(&mut contract);
+        assert_eq!(estimate.storage_bytes, contract.storage_usage() - before);
+        contract.delete_grant(public_key(2), "A1".into(), None);
+
+        let log = serde_json::to_value(contract.audit_log(0, 10)).unwrap();
+        assert_eq!(log.as_array().unwrap().len(), 2);
+        for (entry, action) in log.as_array().unwrap().iter().zip(["insert", "delete"]) {
+            assert_eq!(entry["action"], action);
+            assert_eq!(entry["grant_id"], grant_id);
+            assert_eq!(entry["actor"], "relayer.near");
+            assert_eq!(entry["block_height"], 7);
+        }
+        assert_eq!(contract.audit_log(1, 10).len(), 1);
+        assert!(contract.audit_log(u64::MAX, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "limit must be at most 100")]
+    fn audit_log_caps_the_limit() {
+        FractalRegistry::default().audit_log(0, MAX_RESULTS as u64 + 1);
+    }
+
+    #[test]
+    fn check_owner_consistency_flags_dangling_and_mismatched_ids() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        let dangling = contract.insert_grant_with_options(
+            public_key(2),
+            "A2".into(),
+            None,
+            GrantOptions::default(),
+        );
+        assert_eq!(
+            contract.check_owner_consistency(owner.clone()),
+            ConsistencyReport {
+                indexed: 2,
+                dangling: 0,
+                mismatched: 0
+            }
+        );
+
+        contract.grants_by_id.remove(&dangling);
+        signed_by(&public_key(4));
+        let foreign = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            None,
+            GrantOptions::default(),
+        );
+        contract
+            .grant_ids_by_owner
+            .get_mut(&owner)
+            .unwrap()
+            .push(foreign);
+
+        assert_eq!(
+            contract.check_owner_consistency(owner),
+            ConsistencyReport {
+                indexed: 3,
+                dangling: 1,
+                mismatched: 1
+            }
+        );
+    }
+
+    #[test]
+    fn insert_grant_to_many_inserts_one_grant_per_grantee() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let grantees = vec![public_key(2), public_key(3), public_key(4)];
+
+        contract.insert_grant_to_many(grantees.clone(), "A1".into(), None, None);
+
+        for grantee in grantees {
+            assert!(contract.is_granted(owner.clone(), grantee, "A1".into()));
+        }
+        assert_eq!(contract.find_grant_ids(Some(owner), None, None).len(), 3);
+        assert_eq!(logged_events().len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Grant already exists")]
+    fn insert_grant_to_many_rejects_repeated_grantees() {
+        signed_by(&public_key(1));
+        FractalRegistry::default().insert_grant_to_many(
+            vec![public_key(2), public_key(3), public_key(2)],
+            "A1".into(),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn grant_ids_are_distinct_and_as_long_as_configured() {
+        signed_by(&public_key(1));
+        let grant =
+            FractalRegistry::default().insert_and_get_grant(public_key(2), "A1".into(), None);
+
+        let grant_ids: std::collections::HashSet<String> = (0..1_000)
+            .map(|locked_until| {
+                derive_grant_id(&Grant {
+                    locked_until,
+                    ..grant.clone()
+                })
+            })
+            .collect();
+
+        assert_eq!(grant_ids.len(), 1_000);
+        assert!(grant_ids.iter().all(|id| id.len() == 2 * GRANT_ID_BYTES));
+    }
+
+    #[test]
+    fn common_data_ids_only_lists_data_ids_both_grantees_have() {
+        let (a, b) = (public_key(2), public_key(3));
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(a.clone(), "Y".into(), None);
+        contract.insert_grant(a.clone(), "X".into(), None);
+        signed_by(&public_key(4));
+        contract.insert_grant(b.clone(), "X".into(), None);
+        contract.insert_grant(b.clone(), "Z".into(), None);
+
+        assert_eq!(
+            contract.common_data_ids(a.clone(), b.clone(), 0, 10),
+            vec!["X"]
+        );
+        assert_eq!(
+            contract.common_data_ids(b.clone(), a.clone(), 0, 10),
+            vec!["X"]
+        );
+        assert!(contract.common_data_ids(a, b, 0, 1).is_empty());
+    }
+
+    #[test]
+    fn grants_inserted_by_signature_record_their_sponsor() {
+        let (owner_sk, owner) = signing_key(1);
+        let mut contract = FractalRegistry::default();
+        let nonce = [7u8; 32];
+        let hashed_payload = nep413_hashed_payload(&Nep413Payload {
+            message: contract.insert_grant_by_signature_message(
+                owner.clone(),
+                public_key(2),
+                "A1".into(),
+                None,
+            ),
+            nonce,
+            recipient: contract.grant_message_recipient(),
+            callback_url: None,
+        });
+        let signature = owner_sk.sign(&hashed_payload).to_bytes().to_vec();
+
+        testing_env!(VMContextBuilder::new()
+            .signer_account_pk(public_key(9))
+            .predecessor_account_id("relayer.near".parse().unwrap())
+            .block_timestamp(NOW)
+            .build());
+        contract.insert_grant_by_signature(
+            owner.clone(),
+            public_key(2),
+            "A1".into(),
+            None,
+            nonce.to_vec(),
+            signature,
+        );
+        assert_eq!(logged_events()[0]["data"]["sponsor"], "relayer.near");
+
+        signed_by(&owner);
+        contract.insert_grant(public_key(3), "A1".into(), None);
+
+        let grants =
+            serde_json::to_value(contract.find_grants(Some(owner), None, None, None, None))
+                .unwrap();
+        assert_eq!(grants[0]["sponsor"], "relayer.near");
+        assert!(grants[1].get("sponsor").is_none(), "inserted by its owner");
+    }
+
+    #[test]
+    fn owner_dashboard_counts_and_samples_grants_per_data_id() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        for grantee in 2..9 {
+            contract.insert_grant(public_key(grantee), "A1".into(), None);
+        }
+        contract.insert_grant(public_key(2), "B1".into(), Some(1));
+        contract.insert_grant(public_key(2), "B1".into(), Some(2));
+        contract.insert_grant(public_key(3), "C1".into(), None);
+        contract.insert_grant(public_key(9), "A1".into(), None);
+
+        assert_eq!(
+            contract.owner_dashboard(owner.clone(), 0, 2),
+            vec![
+                DataIdSummary {
+                    data_id: "A1".into(),
+                    grant_count: 8,
+                    sample_grantees: (2..7).map(public_key).collect(),
+                },
+                DataIdSummary {
+                    data_id: "B1".into(),
+                    grant_count: 2,
+                    sample_grantees: vec![public_key(2)],
+                },
+            ]
+        );
+        assert_eq!(
+            contract.owner_dashboard(owner.clone(), 2, 2),
+            vec![DataIdSummary {
+                data_id: "C1".into(),
+                grant_count: 1,
+                sample_grantees: vec![public_key(3)],
+            }]
+        );
+        assert!(contract.owner_dashboard(owner, 3, 2).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch is larger than MAX_BATCH_SIZE (32)")]
+    fn owner_dashboard_rejects_a_limit_that_truncates_into_range() {
+        let contract = FractalRegistry::default();
+
+        contract.owner_dashboard(public_key(1), 0, (1 << 32) + 1);
+    }
+
+    fn with_strict_locked_until() -> FractalRegistry {
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_strict_locked_until(true);
+        signed_by(&public_key(1));
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "locked_until appears to be in the wrong unit")]
+    fn strict_locked_until_rejects_timestamps_in_seconds() {
+        with_strict_locked_until().insert_grant(
+            public_key(2),
+            "A1".into(),
+            Some(NOW / 1_000_000_000),
+        );
+    }
+
+    #[test]
+    fn strict_locked_until_accepts_timestamps_in_nanoseconds() {
+        let mut contract = with_strict_locked_until();
+        contract.insert_grant(public_key(2), "A1".into(), Some(NOW + 1_000));
+        contract.insert_grant(public_key(2), "A2".into(), Some(NOW - 1_000));
+        contract.insert_grant(public_key(2), "A3".into(), Some(0));
+
+        assert_eq!(
+            contract
+                .find_grant_ids(Some(public_key(1)), None, None)
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn owner_grant_ids_after_returns_the_tail_of_the_owners_index() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let mut inserted = vec![];
+        for data_id in ["A1", "A2"] {
+            inserted.push(contract.insert_grant_with_options(
+                public_key(2),
+                data_id.into(),
+                None,
+                GrantOptions::default(),
+            ));
+        }
+        assert_eq!(
+            contract.owner_grant_ids_after(owner.clone(), 0, 10),
+            inserted
+        );
+        assert_eq!(
+            contract.owner_grant_ids_after(owner.clone(), 0, 1),
+            inserted[..1]
+        );
+
+        inserted.push(contract.insert_grant_with_options(
+            public_key(2),
+            "A3".into(),
+            None,
+            GrantOptions::default(),
+        ));
+        assert_eq!(
+            contract.owner_grant_ids_after(owner.clone(), 2, 10),
+            inserted[2..]
+        );
+        assert!(contract.owner_grant_ids_after(owner, 3, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "limit must be at most 100")]
+    fn owner_grant_ids_after_caps_the_limit() {
+        FractalRegistry::default().owner_grant_ids_after(public_key(1), 0, MAX_RESULTS as u64 + 1);
+    }
+
+    #[test]
+    fn compute_grant_ids_matches_the_ids_grants_are_stored_under() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        let inserted = contract.insert_grant_with_options(
+            public_key(2),
+            "A1".into(),
+            Some(NOW + 1),
+            labeled("kyc"),
+        );
+        let exported = contract.export_owner_grants(owner.clone(), None, None);
+        let missing = GrantInput {
+            data_id: "A2".into(),
+            ..exported[0].clone()
+        };
+
+        let computed =
+            contract.compute_grant_ids(vec![exported[0].clone(), missing], owner.clone());
+
+        assert_eq!(computed[0], inserted);
+        assert_eq!(
+            contract.get_grant(computed[0].clone()).unwrap().data_id,
+            "A1"
+        );
+        assert_eq!(
+            contract.find_grant_ids(Some(owner), None, Some("A1".into())),
+            vec![computed[0].clone()]
+        );
+        assert!(contract.get_grant(computed[1].clone()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch is larger than MAX_BATCH_SIZE (32)")]
+    fn compute_grant_ids_rejects_an_oversized_batch() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        let exported = contract.export_owner_grants(owner.clone(), None, None);
+
+        contract.compute_grant_ids(vec![exported[0].clone(); MAX_BATCH_SIZE + 1], owner);
+    }
+
+    #[test]
+    fn silent_batch_inserts_emit_no_events() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant_multi(
+            public_key(2),
+            vec!["A1".into(), "A2".into()],
+            None,
+            Some(true),
+        );
+        contract.insert_grant_to_many(
+            vec![public_key(3), public_key(4)],
+            "A1".into(),
+            None,
+            Some(true),
+        );
+        let exported = contract.export_owner_grants(owner.clone(), None, None);
+        signed_by(&public_key(5));
+        contract.import_owner_grants(exported, Some(true));
+
+        assert!(get_logs().is_empty());
+        assert_eq!(contract.find_grant_ids(Some(owner), None, None).len(), 4);
+        assert_eq!(
+            contract
+                .find_grant_ids(Some(public_key(5)), None, None)
+                .len(),
+            4
+        );
+
+        contract.insert_grant_multi(public_key(2), vec!["A3".into()], None, Some(false));
+        assert_eq!(logged_events()[0]["event"], "grant_inserted");
+    }
+
+    #[test]
+    fn has_relationship_checks_for_any_grant_between_owner_and_grantee() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        signed_by(&public_key(3));
+        contract.insert_grant(public_key(4), "A1".into(), None);
+
+        assert!(contract.has_relationship(owner.clone(), public_key(2)));
+        assert!(!contract.has_relationship(owner.clone(), public_key(4)));
+        assert!(!contract.has_relationship(public_key(3), public_key(2)));
+        assert!(!contract.has_relationship(owner, public_key(5)));
+    }
+
+    #[test]
+    fn find_grants_partitioned_splits_active_from_inactive_grants() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(public_key(2), "active".into(), None);
+        contract.insert_grant_with_options(
+            public_key(2),
+            "expiring".into(),
+            None,
+            GrantOptions {
+                expires_at: Some(NOW + 10),
+                ..GrantOptions::default()
+            },
+        );
+        contract.insert_grant_with_options(
+            public_key(2),
+            "pending".into(),
+            None,
+            GrantOptions {
+                valid_from: Some(NOW + 10),
+                ..GrantOptions::default()
+            },
+        );
+        contract.insert_grant_with_options(
+            public_key(2),
+            "unattested".into(),
+            None,
+            GrantOptions {
+                required_attestor: Some("attestor.near".parse().unwrap()),
+                ..GrantOptions::default()
+            },
+        );
+        let data_ids = |grants: &Vec<Grant>| -> Vec<String> {
+            grants.iter().map(|grant| grant.data_id.clone()).collect()
+        };
+
+        let partitioned = contract.find_grants_partitioned(Some(owner.clone()), None, None);
+        assert_eq!(data_ids(&partitioned.active), vec!["active", "expiring"]);
+        assert_eq!(
+            data_ids(&partitioned.inactive),
+            vec!["pending", "unattested"]
+        );
+
+        signed_by_at(&owner, NOW + 10);
+        let partitioned = contract.find_grants_partitioned(Some(owner), Some(public_key(2)), None);
+        assert_eq!(data_ids(&partitioned.active), vec!["active", "pending"]);
+        assert_eq!(
+            data_ids(&partitioned.inactive),
+            vec!["expiring", "unattested"]
+        );
+    }
+
+    fn grantee_consent(
+        contract: &FractalRegistry,
+        grantee_sk: &SigningKey,
+        owner: &PublicKey,
+        grantee: &PublicKey,
+        locked_until: EpochHeight,
+    ) -> Vec<u8> {
+        let hashed_payload = nep413_hashed_payload(&Nep413Payload {
+            message: contract.delete_grant_with_grantee_consent_message(
+                owner.clone(),
+                grantee.clone(),
+                "A1".into(),
+                locked_until,
+            ),
+            nonce: [7u8; 32],
+            recipient: contract.grant_message_recipient(),
+            callback_url: None,
+        });
+
+        grantee_sk.sign(&hashed_payload).to_bytes().to_vec()
+    }
+
+    #[test]
+    fn grantee_consent_lets_the_owner_delete_a_timelocked_grant() {
+        let owner = public_key(1);
+        let (grantee_sk, grantee) = signing_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), Some(NOW + 10));
+        let signature = grantee_consent(&contract, &grantee_sk, &owner, &grantee, NOW + 10);
+
+        signed_by(&owner);
+        contract.delete_grant_with_grantee_consent(
+            grantee.clone(),
+            "A1".into(),
+            NOW + 10,
+            vec![7u8; 32],
+            signature,
+        );
+
+        let events = logged_events();
+        assert_eq!(events[0]["event"], "grant_revoked_with_consent");
+        assert_eq!(events[0]["data"]["locked_until"], NOW + 10);
+        assert_eq!(events[1]["event"], "grant_deleted");
+        assert!(!contract.is_granted(owner, grantee, "A1".into()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Consent was already used")]
+    fn grantee_consent_cant_be_replayed_on_a_recreated_grant() {
+        let owner = public_key(1);
+        let (grantee_sk, grantee) = signing_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), Some(NOW + 10));
+        let signature = grantee_consent(&contract, &grantee_sk, &owner, &grantee, NOW + 10);
+        contract.delete_grant_with_grantee_consent(
+            grantee.clone(),
+            "A1".into(),
+            NOW + 10,
+            vec![7u8; 32],
+            signature.clone(),
+        );
+
+        contract.insert_grant(grantee.clone(), "A1".into(), Some(NOW + 10));
+        contract.delete_grant_with_grantee_consent(
+            grantee,
+            "A1".into(),
+            NOW + 10,
+            vec![7u8; 32],
+            signature,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Signature doesn't match")]
+    fn grantee_consent_signed_by_someone_else_is_rejected() {
+        let owner = public_key(1);
+        let (_, grantee) = signing_key(2);
+        let (other_sk, _) = signing_key(3);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), Some(NOW + 10));
+        let signature = grantee_consent(&contract, &other_sk, &owner, &grantee, NOW + 10);
+
+        contract.delete_grant_with_grantee_consent(
+            grantee,
+            "A1".into(),
+            NOW + 10,
+            vec![7u8; 32],
+            signature,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Signature doesn't match")]
+    fn grantee_consent_covers_only_the_grant_it_was_signed_for() {
+        let owner = public_key(1);
+        let (grantee_sk, grantee) = signing_key(2);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        contract.insert_grant(grantee.clone(), "A1".into(), Some(NOW + 10));
+        contract.insert_grant(grantee.clone(), "A1".into(), Some(NOW + 20));
+        let signature = grantee_consent(&contract, &grantee_sk, &owner, &grantee, NOW + 10);
+
+        contract.delete_grant_with_grantee_consent(
+            grantee,
+            "A1".into(),
+            NOW + 20,
+            vec![7u8; 32],
+            signature,
+        );
+    }
+
+    #[test]
+    fn recent_grants_for_owner_returns_the_newest_grants_first() {
+        let owner = public_key(1);
+        signed_by(&owner);
+        let mut contract = FractalRegistry::default();
+        for data_id in ["A1", "A2", "A3", "A4"] {
+            contract.insert_grant(public_key(2), data_id.into(), None);
+        }
+        let data_ids = |grants: Vec<Grant>| -> Vec<String> {
+            grants.into_iter().map(|grant| grant.data_id).collect()
+        };
+
+        assert_eq!(
+            data_ids(contract.recent_grants_for_owner(owner.clone(), 2)),
+            vec!["A4", "A3"]
+        );
+        assert_eq!(
+            data_ids(contract.recent_grants_for_owner(owner.clone(), 10)),
+            vec!["A4", "A3", "A2", "A1"]
+        );
+        assert!(contract
+            .recent_grants_for_owner(public_key(3), 2)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "n must be at most 100")]
+    fn recent_grants_for_owner_caps_n() {
+        signed_by(&public_key(1));
+        FractalRegistry::default().recent_grants_for_owner(public_key(1), MAX_RESULTS as u64 + 1);
+    }
+
+    #[cfg(feature = "gas-profiling")]
+    #[test]
+    fn profiled_methods_log_the_gas_they_used() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+
+        contract.insert_grant(public_key(2), "A1".into(), None);
+        contract.find_grants(Some(public_key(1)), None, None, None, None);
+        contract.delete_grant(public_key(2), "A1".into(), None);
+
+        let profiles: Vec<String> = get_logs()
+            .into_iter()
+            .filter(|log| log.starts_with("GAS_PROFILE: "))
+            .collect();
+        assert_eq!(
+            profiles.len(),
+            2,
+            "find_grants is a view, so it isn't profiled"
+        );
+        for (profile, method) in profiles.iter().zip(["insert_grant", "delete_grant"]) {
+            let used: u64 = profile
+                .strip_prefix(&format!("GAS_PROFILE: {} ", method))
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!(used > 0);
+        }
+    }
+
+    #[test]
+    fn claimants_and_their_delegates_can_grant_on_a_claimed_data_id() {
+        let claimant = public_key(1);
+        let delegate = public_key(5);
+        let mut contract = with_data_id_claims();
+        signed_by(&claimant);
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+        contract.add_claim_delegate(delegate.clone());
+        contract.insert_grant(public_key(3), "user123/passport".into(), None);
+
+        signed_by(&delegate);
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+
+        assert!(contract.is_claim_delegate(claimant.clone(), delegate.clone()));
+        assert!(contract.is_granted(delegate, public_key(2), "user123/passport".into()));
+        assert_eq!(
+            contract.data_id_claimant("user123/passport".into()),
+            Some(claimant)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "data_id owned by another account")]
+    fn removed_claim_delegates_cant_grant_on_a_claimed_data_id() {
+        let delegate = public_key(5);
+        let mut contract = with_data_id_claims();
+        signed_by(&public_key(1));
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+        contract.add_claim_delegate(delegate.clone());
+        contract.remove_claim_delegate(delegate.clone());
+
+        signed_by(&delegate);
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "data_id owned by another account")]
+    fn another_owners_delegates_cant_grant_on_a_claimed_data_id() {
+        let stranger = public_key(5);
+        let mut contract = with_data_id_claims();
+        signed_by(&public_key(1));
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+        signed_by(&public_key(4));
+        contract.add_claim_delegate(stranger.clone());
+
+        signed_by(&stranger);
+        contract.insert_grant(public_key(2), "user123/passport".into(), None);
+    }
+
+    #[test]
+    fn claim_delegate_changes_are_announced_once() {
+        let (claimant, delegate) = (public_key(1), public_key(5));
+        signed_by(&claimant);
+        let mut contract = FractalRegistry::default();
+
+        contract.add_claim_delegate(delegate.clone());
+        contract.add_claim_delegate(delegate.clone());
+        contract.remove_claim_delegate(delegate.clone());
+        contract.remove_claim_delegate(delegate.clone());
+
+        let events = logged_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event"], "claim_delegate_added");
+        assert_eq!(events[1]["event"], "claim_delegate_removed");
+        for event in &events {
+            assert_eq!(event["version"], "2.7.0");
+            assert_eq!(event["data"]["claimant"], serde_json::json!(claimant));
+            assert_eq!(event["data"]["delegate"], serde_json::json!(delegate));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Contract is paused")]
+    fn claim_delegates_cant_change_while_paused() {
+        let mut contract = FractalRegistry::default();
+        called_by_contract();
+        contract.set_paused(true);
+
+        signed_by(&public_key(1));
+        contract.add_claim_delegate(public_key(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's grants are frozen")]
+    fn frozen_owners_cant_add_claim_delegates() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.freeze_own_grants();
+
+        contract.add_claim_delegate(public_key(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Owner's grants are frozen")]
+    fn frozen_owners_cant_remove_claim_delegates() {
+        signed_by(&public_key(1));
+        let mut contract = FractalRegistry::default();
+        contract.add_claim_delegate(public_key(5));
+        contract.freeze_own_grants();
+
+        contract.remove_claim_delegate(public_key(5));
     }
 }
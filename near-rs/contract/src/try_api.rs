@@ -0,0 +1,60 @@
+use near_sdk::{env, near_bindgen, EpochHeight, PublicKey};
+
+use super::{FractalRegistry, FractalRegistryExt, Grant, GrantFields, GrantOptions, RegistryError};
+
+// Result-returning versions of the main operations, for Rust callers (e.g. other contracts built against
+// this crate) that want to match on a `RegistryError` instead of parsing panic messages. They run the
+// exact same checks as their panicking counterparts, and called from outside, an Err still fails the call
+// with that counterpart's message.
+#[near_bindgen]
+impl FractalRegistry {
+    #[handle_result]
+    pub fn try_insert_grant(
+        &mut self,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+        options: Option<GrantOptions>,
+    ) -> Result<String, RegistryError> {
+        self._try_insert_grant(
+            env::signer_account_pk(),
+            grantee,
+            data_id,
+            locked_until,
+            options.unwrap_or_default(),
+        )
+    }
+
+    #[handle_result]
+    pub fn try_delete_grant(
+        &mut self,
+        grantee: PublicKey,
+        data_id: String,
+        locked_until: Option<EpochHeight>,
+    ) -> Result<u64, RegistryError> {
+        self._try_delete_grant(
+            env::signer_account_pk(),
+            grantee,
+            data_id,
+            locked_until,
+            None,
+        )
+    }
+
+    #[handle_result]
+    pub fn try_delete_grant_checked(
+        &mut self,
+        grant_id: String,
+        expected: GrantFields,
+    ) -> Result<(), RegistryError> {
+        self._try_delete_grant_checked(grant_id, expected)
+    }
+
+    #[handle_result]
+    pub fn try_get_grant(&self, grant_id: String) -> Result<Grant, RegistryError> {
+        self.grants_by_id
+            .get(&grant_id)
+            .cloned()
+            .ok_or(RegistryError::GrantDoesNotExist)
+    }
+}
@@ -35,17 +35,21 @@ async fn test_everything() -> anyhow::Result<()> {
         .await?;
     assert!(result.is_success());
     assert_eq!(result.logs().len(), 1);
+    let event = extract_event(result.logs()[0]);
+    assert!(event["data"]["block_height"].is_u64());
     assert_eq!(
-        extract_event(result.logs()[0]),
+        event,
         json!({
             "standard": "FractalRegistry",
-            "version": "0",
+            "version": "2.7.0",
             "event": "grant_inserted",
             "data": {
                 "owner": test_public_key,
                 "grantee": bob,
                 "data_id": "A1",
                 "locked_until": 0,
+                "block_height": event["data"]["block_height"],
+                "predecessor": test_account.id(),
             },
         }),
     );
@@ -212,6 +216,14 @@ async fn test_everything() -> anyhow::Result<()> {
         .unwrap();
     assert_eq!(grants, vec![]);
 
+    let bob_a1_grant_id = test_account
+        .call(contract.id(), "find_grants_with_embedded_id")
+        .args_json(json!({"owner": test_public_key, "grantee": bob, "data_id": "A1"}))
+        .view()
+        .await?
+        .json::<serde_json::Value>()?[0]["grant_id"]
+        .clone();
+
     result = test_account
         .call(contract.id(), "delete_grant")
         .args_json(json!({"grantee": bob, "data_id": "A1"}))
@@ -219,17 +231,22 @@ async fn test_everything() -> anyhow::Result<()> {
         .await?;
     assert!(result.is_success());
     assert_eq!(result.logs().len(), 1);
+    let event = extract_event(result.logs()[0]);
+    assert!(event["data"]["block_height"].is_u64());
     assert_eq!(
-        extract_event(result.logs()[0]),
+        event,
         json!({
             "standard": "FractalRegistry",
-            "version": "0",
+            "version": "2.7.0",
             "event": "grant_deleted",
             "data": {
                 "owner": test_public_key,
                 "grantee": bob,
                 "data_id": "A1",
                 "locked_until": 0,
+                "grant_id": bob_a1_grant_id,
+                "block_height": event["data"]["block_height"],
+                "predecessor": test_account.id(),
             },
         })
     );